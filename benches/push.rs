@@ -0,0 +1,93 @@
+//! Throughput and allocations of pushing and of the push/pop round trip.
+//!
+//! Run with `cargo bench --bench push`.
+//!
+//! - `push`: items are pushed into a preallocated queue, nothing waits
+//! - `round trip`: every pushed item is popped right away
+//! - `wheel push`: the same pushes into the timing wheel backend
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    future::Future,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+use tokio::runtime::{Builder, Runtime};
+use tokio_delayed_queue::{Backend, DelayedQueue};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Аллокатор, считающий количество аллокаций
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+////////////////////////////////////////////////////////////////////////////////
+
+const ITEMS: usize = 1_000_000;
+
+/// Прогоняем сценарий над заранее созданной очередью,
+/// печатаем пропускную способность и аллокации на итем
+fn measure<F, Fut>(name: &str, runtime: &Runtime, queue: DelayedQueue<usize>, scenario: F)
+where
+    F: FnOnce(DelayedQueue<usize>) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+
+    runtime.block_on(scenario(queue));
+
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+
+    println!(
+        "{name:<12} {:>10.0} items/s, {:.2} allocations/item",
+        ITEMS as f64 / elapsed.as_secs_f64(),
+        allocations as f64 / ITEMS as f64,
+    );
+}
+
+/// Только добавление
+async fn push(queue: DelayedQueue<usize>) {
+    for item in 0..ITEMS {
+        queue.push(item, Duration::from_secs(60)).await.unwrap();
+    }
+}
+
+/// Добавление и сразу же извлечение
+async fn round_trip(queue: DelayedQueue<usize>) {
+    for item in 0..ITEMS {
+        queue.push(item, Duration::ZERO).await.unwrap();
+        queue.pop().await;
+    }
+}
+
+fn main() {
+    let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+
+    // Очереди создаем заранее, чтобы не учитывать их начальную аллокацию
+    let wheel = DelayedQueue::builder(ITEMS)
+        .backend(Backend::TimingWheel {
+            resolution: Duration::from_millis(1),
+        })
+        .build();
+
+    measure("push", &runtime, DelayedQueue::new(ITEMS), push);
+    measure("round trip", &runtime, DelayedQueue::new(1), round_trip);
+    measure("wheel push", &runtime, wheel, push);
+}
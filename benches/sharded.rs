@@ -0,0 +1,107 @@
+//! Single lock queue against sharded queue under high producer contention.
+//!
+//! Run with `cargo bench --bench sharded`. Contention shows up only with several
+//! CPU cores, the runtime uses all available ones.
+
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+use tokio::runtime::Builder;
+use tokio_delayed_queue::{DelayedQueue, PushError, ShardedDelayedQueue};
+
+////////////////////////////////////////////////////////////////////////////////
+
+const PRODUCERS: usize = 64;
+const CONSUMERS: usize = 8;
+const ITEMS_PER_PRODUCER: usize = 10_000;
+const SIZE: usize = 4096;
+
+/// Очередь, через которую гоняем итемы
+trait BenchQueue: Clone + Send + Sync + 'static {
+    fn push(&self, item: usize) -> impl Future<Output = Result<(), PushError<usize>>> + Send;
+    fn pop(&self) -> impl Future<Output = usize> + Send;
+}
+
+impl BenchQueue for DelayedQueue<usize> {
+    fn push(&self, item: usize) -> impl Future<Output = Result<(), PushError<usize>>> + Send {
+        DelayedQueue::push(self, item, Duration::ZERO)
+    }
+
+    fn pop(&self) -> impl Future<Output = usize> + Send {
+        DelayedQueue::pop(self)
+    }
+}
+
+impl BenchQueue for ShardedDelayedQueue<usize> {
+    fn push(&self, item: usize) -> impl Future<Output = Result<(), PushError<usize>>> + Send {
+        ShardedDelayedQueue::push(self, item, Duration::ZERO)
+    }
+
+    fn pop(&self) -> impl Future<Output = usize> + Send {
+        ShardedDelayedQueue::pop(self)
+    }
+}
+
+/// Много отправителей и несколько получателей на многопоточном рантайме
+fn run<Q: BenchQueue>(name: &str, queue: Q) {
+    let workers = std::thread::available_parallelism().map_or(1, usize::from);
+
+    let runtime = Builder::new_multi_thread()
+        .worker_threads(workers)
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let items = PRODUCERS * ITEMS_PER_PRODUCER;
+
+    let elapsed = runtime.block_on(async {
+        let start = Instant::now();
+
+        let consumers = (0..CONSUMERS)
+            .map(|_| {
+                let queue = queue.clone();
+                tokio::spawn(async move {
+                    for _ in 0..items / CONSUMERS {
+                        queue.pop().await;
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let producers = (0..PRODUCERS)
+            .map(|producer| {
+                let queue = queue.clone();
+                tokio::spawn(async move {
+                    for item in 0..ITEMS_PER_PRODUCER {
+                        queue
+                            .push(producer * ITEMS_PER_PRODUCER + item)
+                            .await
+                            .unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for handle in producers.into_iter().chain(consumers) {
+            handle.await.unwrap();
+        }
+
+        start.elapsed()
+    });
+
+    println!(
+        "{name:<16} {:>10.0} items/s",
+        items as f64 / elapsed.as_secs_f64()
+    );
+}
+
+fn main() {
+    run("single lock", DelayedQueue::new(SIZE));
+    for shards in [4, 8, 16] {
+        run(
+            &format!("{shards} shards"),
+            ShardedDelayedQueue::new(SIZE, shards),
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use crate::{
+    clock::{Clock, DefaultClock},
+    jitter::Jitter,
+    overflow::OverflowPolicy,
+    queue::{DelayedQueue, ItemCallback},
+    rng::{RandomSource, SplitMix64},
+    storage::Backend,
+    storage::Weigher,
+    store::DelayStore,
+};
+use std::{io, time::Duration};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Builder of [`DelayedQueue`] with optional settings.
+///
+/// ```rust
+/// # use tokio_delayed_queue::{DelayedQueue, Jitter, SplitMix64};
+/// let queue: DelayedQueue<u32> = DelayedQueue::builder(16)
+///     .jitter(Jitter::Bounded { percent: 10 })
+///     .random_source(SplitMix64::new(42))
+///     .build();
+/// ```
+pub struct DelayedQueueBuilder<T, C = DefaultClock> {
+    /// Максимальный размер очереди
+    pub(super) size: usize,
+
+    /// Разброс задержек по умолчанию
+    pub(super) jitter: Option<Jitter>,
+
+    /// Генератор случайных чисел для разброса
+    pub(super) random_source: Box<dyn RandomSource>,
+
+    /// Таймаут видимости итемов, извлеченных с подтверждением
+    pub(super) visibility_timeout: Duration,
+
+    /// Максимальное опоздание и куда отдаем опоздавшие итемы
+    pub(super) lateness: Option<(Duration, ItemCallback<T>)>,
+
+    /// Куда отдаем протухшие итемы
+    pub(super) on_expired: Option<ItemCallback<T>>,
+
+    /// Куда отдаем ошибки хранилища
+    pub(super) on_store_error: Option<Box<dyn Fn(io::Error) + Send + Sync>>,
+
+    /// Пересчитывать ли время итемов по часам при пробуждении
+    pub(super) recheck_wall_clock: bool,
+
+    /// Источник времени
+    pub(super) clock: C,
+
+    /// Обслуживать ли футуры извлечения строго по очереди
+    pub(super) fair: bool,
+
+    /// Как хранятся итемы
+    pub(super) backend: Backend,
+
+    /// Шаг округления сроков для объединения пробуждений
+    pub(super) resolution: Option<Duration>,
+
+    /// Максимальный суммарный вес итемов и как его считать
+    pub(super) weight: Option<(usize, Weigher<T>)>,
+
+    /// Что делаем при переполнении
+    pub(super) overflow_policy: OverflowPolicy,
+
+    /// Куда отдаем вытесненные и выкинутые при переполнении итемы
+    pub(super) on_evicted: Option<ItemCallback<T>>,
+}
+
+impl<T> DelayedQueueBuilder<T> {
+    /// Creates builder of queue with fixed capacity.
+    pub fn new(size: usize) -> DelayedQueueBuilder<T> {
+        DelayedQueueBuilder {
+            size,
+            jitter: None,
+            random_source: Box::new(SplitMix64::from_time()),
+            visibility_timeout: Duration::from_secs(30),
+            lateness: None,
+            on_expired: None,
+            on_store_error: None,
+            recheck_wall_clock: false,
+            clock: DefaultClock::default(),
+            fair: false,
+            backend: Backend::Fifo,
+            resolution: None,
+            weight: None,
+            overflow_policy: OverflowPolicy::Wait,
+            on_evicted: None,
+        }
+    }
+}
+
+impl<T, C: Clock> DelayedQueueBuilder<T, C> {
+    /// Clock used for deadlines and sleeping, [`DefaultClock`] by default.
+    pub fn clock<C2: Clock>(self, clock: C2) -> DelayedQueueBuilder<T, C2> {
+        DelayedQueueBuilder {
+            size: self.size,
+            jitter: self.jitter,
+            random_source: self.random_source,
+            visibility_timeout: self.visibility_timeout,
+            lateness: self.lateness,
+            on_expired: self.on_expired,
+            on_store_error: self.on_store_error,
+            recheck_wall_clock: self.recheck_wall_clock,
+            clock,
+            fair: self.fair,
+            backend: self.backend,
+            resolution: self.resolution,
+            weight: self.weight,
+            overflow_policy: self.overflow_policy,
+            on_evicted: self.on_evicted,
+        }
+    }
+
+    /// Jitter applied to every push delay unless overridden per push.
+    pub fn jitter(mut self, jitter: Jitter) -> DelayedQueueBuilder<T, C> {
+        self.jitter = Some(jitter);
+        self
+    }
+
+    /// Random source used for jitter. Time seeded [`SplitMix64`] by default.
+    pub fn random_source<R>(mut self, random_source: R) -> DelayedQueueBuilder<T, C>
+    where
+        R: RandomSource + 'static,
+    {
+        self.random_source = Box::new(random_source);
+        self
+    }
+
+    /// Time after which item popped with `pop_ack` becomes visible again
+    /// unless acknowledged. 30 seconds by default.
+    pub fn visibility_timeout(mut self, timeout: Duration) -> DelayedQueueBuilder<T, C> {
+        self.visibility_timeout = timeout;
+        self
+    }
+
+    /// Items popped later than `max_lateness` after their scheduled time are passed
+    /// to `dead_letter` instead of being returned to `pop` callers.
+    ///
+    /// Lateness is checked lazily when a pop future takes the item: while consumers are stalled,
+    /// overdue items stay in the queue and are diverted by the next pop.
+    pub fn max_lateness<F>(
+        mut self,
+        max_lateness: Duration,
+        dead_letter: F,
+    ) -> DelayedQueueBuilder<T, C>
+    where
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        self.lateness = Some((max_lateness, Box::new(dead_letter)));
+        self
+    }
+
+    /// Items whose TTL expired before they could be popped are passed to `on_expired`
+    /// instead of being silently discarded.
+    pub fn on_expired<F>(mut self, on_expired: F) -> DelayedQueueBuilder<T, C>
+    where
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        self.on_expired = Some(Box::new(on_expired));
+        self
+    }
+
+    /// Re-checks wall clock whenever an item pushed with `push_at_system_time`
+    /// is examined, so wall clock jumps move its deadline accordingly.
+    /// Futures waiting for such items wake up at least once a second to notice forward jumps.
+    pub fn recheck_wall_clock(mut self, recheck: bool) -> DelayedQueueBuilder<T, C> {
+        self.recheck_wall_clock = recheck;
+        self
+    }
+
+    /// Fair mode: waiting pop futures pick items strictly in order of their creation,
+    /// so no consumer starves under sustained load. A future sleeping on an item
+    /// it has reserved lets the next one pick meanwhile. Disabled by default,
+    /// then whichever future polls first after a handoff gets the item.
+    pub fn fair(mut self, fair: bool) -> DelayedQueueBuilder<T, C> {
+        self.fair = fair;
+        self
+    }
+
+    /// Storage backend of items, [`Backend::Fifo`] by default.
+    /// Use [`Backend::TimingWheel`] for millions of pending items.
+    pub fn backend(mut self, backend: Backend) -> DelayedQueueBuilder<T, C> {
+        self.backend = backend;
+        self
+    }
+
+    /// Bounds total weight of items by `max_weight` in addition to the item count.
+    /// Weight of an item is computed by `weigher` once on push, push waits
+    /// until enough weight is freed. Item heavier than `max_weight` is never pushed,
+    /// push returns it back with [`PushError::TooHeavy`](crate::PushError::TooHeavy).
+    pub fn max_weight<F>(mut self, max_weight: usize, weigher: F) -> DelayedQueueBuilder<T, C>
+    where
+        F: Fn(&T) -> usize + Send + Sync + 'static,
+    {
+        self.weight = Some((max_weight, Box::new(weigher)));
+        self
+    }
+
+    /// What push does when the queue is full, [`OverflowPolicy::Wait`] by default.
+    pub fn overflow_policy(mut self, policy: OverflowPolicy) -> DelayedQueueBuilder<T, C> {
+        self.overflow_policy = policy;
+        self
+    }
+
+    /// Items evicted or dropped because of the overflow policy are passed to `on_evicted`
+    /// instead of being silently discarded.
+    pub fn on_evicted<F>(mut self, on_evicted: F) -> DelayedQueueBuilder<T, C>
+    where
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        self.on_evicted = Some(Box::new(on_evicted));
+        self
+    }
+
+    /// Rounds item deadlines up to a multiple of `resolution` and coalesces wakeups:
+    /// pop futures waiting for items of one slot share a single timer, so one wake
+    /// releases every item due within the slot. Lateness is measured from the rounded deadline.
+    /// Exact deadlines by default.
+    pub fn resolution(mut self, resolution: Duration) -> DelayedQueueBuilder<T, C> {
+        self.resolution = Some(resolution);
+        self
+    }
+
+    /// Callback receiving errors of the store set with [`build_with_store`](Self::build_with_store)
+    /// which have no caller to return them to: removals, re-armed periodic items and leases.
+    /// Failure to save a pushed item is returned from push as [`PushError::Store`](crate::PushError::Store).
+    /// Such errors are ignored by default.
+    pub fn on_store_error<F>(mut self, on_store_error: F) -> DelayedQueueBuilder<T, C>
+    where
+        F: Fn(io::Error) + Send + Sync + 'static,
+    {
+        self.on_store_error = Some(Box::new(on_store_error));
+        self
+    }
+
+    /// Creates the queue.
+    pub fn build(self) -> DelayedQueue<T, C> {
+        DelayedQueue::from_builder(self)
+    }
+
+    /// Creates the queue backed by persistent `store`, previously stored items are
+    /// loaded into the queue with their wall-clock deadlines.
+    /// Recurrence of periodic items is not stored, only their next occurrence.
+    /// TTL of items is not stored either, restored items never expire.
+    pub fn build_with_store<S>(self, store: S) -> io::Result<DelayedQueue<T, C>>
+    where
+        S: DelayStore<T> + 'static,
+    {
+        DelayedQueue::from_store(self, store)
+    }
+}
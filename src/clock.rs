@@ -0,0 +1,279 @@
+use parking_lot::Mutex;
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant, SystemTime},
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Timer used by pop futures for sleeping until the deadline of the reserved item.
+pub trait Timer: Send + Sync + 'static {
+    /// Future completing at the deadline. It does not have to be `Unpin`,
+    /// pop futures store it inline and never move it after pinning.
+    type Sleep: Future<Output = ()> + Send;
+
+    /// Sleeps until `deadline`.
+    fn sleep_until(&self, deadline: Instant) -> Self::Sleep;
+}
+
+/// Source of time for the queue: current time for computing deadlines
+/// and a [`Timer`] for sleeping until them.
+pub trait Clock: Timer {
+    /// Current monotonic time.
+    fn now(&self) -> Instant;
+
+    /// Current wall-clock time, used for items with wall-clock deadlines.
+    /// [`SystemTime::now`] by default.
+    fn system_now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Clock used by queues by default: [`TokioClock`] with `tokio` feature,
+/// otherwise `AsyncIoClock` with `async-io` feature or `ThreadClock` with `thread-timer` feature.
+#[cfg(feature = "tokio")]
+pub type DefaultClock = TokioClock;
+
+/// Clock used by queues by default: [`TokioClock`] with `tokio` feature,
+/// otherwise `AsyncIoClock` with `async-io` feature or `ThreadClock` with `thread-timer` feature.
+#[cfg(all(not(feature = "tokio"), feature = "async-io"))]
+pub type DefaultClock = AsyncIoClock;
+
+/// Clock used by queues by default: [`TokioClock`] with `tokio` feature,
+/// otherwise `AsyncIoClock` with `async-io` feature or `ThreadClock` with `thread-timer` feature.
+#[cfg(all(
+    not(feature = "tokio"),
+    not(feature = "async-io"),
+    feature = "thread-timer"
+))]
+pub type DefaultClock = crate::thread_timer::ThreadClock;
+
+#[cfg(not(any(feature = "tokio", feature = "async-io", feature = "thread-timer")))]
+compile_error!("One of `tokio`, `async-io` or `thread-timer` features must be enabled");
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Clock based on Tokio timer, follows paused Tokio time in tests.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioClock;
+
+#[cfg(feature = "tokio")]
+impl Timer for TokioClock {
+    type Sleep = tokio::time::Sleep;
+
+    fn sleep_until(&self, deadline: Instant) -> Self::Sleep {
+        tokio::time::sleep_until(deadline.into())
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Clock based on `async-io` timer, for smol and other `async-io` based executors.
+#[cfg(feature = "async-io")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AsyncIoClock;
+
+/// Sleep future of [`AsyncIoClock`].
+#[cfg(feature = "async-io")]
+#[derive(Debug)]
+pub struct AsyncIoSleep(async_io::Timer);
+
+#[cfg(feature = "async-io")]
+impl Future for AsyncIoSleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Таймер сам по себе Unpin, момент срабатывания нам не нужен
+        Pin::new(&mut self.0).poll(cx).map(|_| ())
+    }
+}
+
+#[cfg(feature = "async-io")]
+impl Timer for AsyncIoClock {
+    type Sleep = AsyncIoSleep;
+
+    fn sleep_until(&self, deadline: Instant) -> Self::Sleep {
+        AsyncIoSleep(async_io::Timer::at(deadline))
+    }
+}
+
+#[cfg(feature = "async-io")]
+impl Clock for AsyncIoClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Состояние ручных часов
+#[derive(Debug)]
+struct ManualState {
+    /// Текущее время
+    now: Instant,
+
+    /// Текущее время по часам, может прыгать независимо от монотонного
+    system_now: SystemTime,
+
+    /// Ожидающие футуры сна по идентификаторам
+    sleepers: BTreeMap<u64, (Instant, Waker)>,
+
+    /// Счетчик идентификаторов футур сна
+    next_id: u64,
+}
+
+/// Manually driven clock for deterministic simulation tests, no runtime timer is used.
+/// Time moves only with [`ManualClock::advance`], which wakes poppers of due items.
+/// Wall clock jumps are simulated with [`ManualClock::set_system_time`].
+///
+/// ```rust
+/// # use tokio_delayed_queue::{DelayedQueue, ManualClock};
+/// # use std::time::Duration;
+/// # tokio_test::block_on(async {
+///
+/// let clock = ManualClock::new();
+/// let queue = DelayedQueue::builder(16).clock(clock.clone()).build();
+///
+/// queue.push(1, Duration::from_secs(3600)).await.unwrap();
+/// clock.advance(Duration::from_secs(3600));
+///
+/// assert_eq!(queue.pop().await, 1);
+///
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    state: Arc<Mutex<ManualState>>,
+}
+
+impl ManualClock {
+    /// Creates clock starting at the current time.
+    pub fn new() -> ManualClock {
+        ManualClock {
+            state: Arc::new(Mutex::new(ManualState {
+                now: Instant::now(),
+                system_now: SystemTime::now(),
+                sleepers: BTreeMap::new(),
+                next_id: 0,
+            })),
+        }
+    }
+
+    /// Moves time forward and wakes sleepers whose deadline is reached.
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.state.lock();
+
+        state.now += duration;
+        state.system_now += duration;
+
+        // Забираем все сработавшие футуры
+        let now = state.now;
+        let due = state
+            .sleepers
+            .iter()
+            .filter(|(_, (deadline, _))| *deadline <= now)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        let wakers = due
+            .into_iter()
+            .filter_map(|id| state.sleepers.remove(&id))
+            .map(|(_, waker)| waker)
+            .collect::<Vec<_>>();
+
+        // Будим уже без блокировки
+        drop(state);
+
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    /// Sets wall-clock time without moving monotonic time, like a system clock adjustment.
+    pub fn set_system_time(&self, time: SystemTime) {
+        self.state.lock().system_now = time;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        ManualClock::new()
+    }
+}
+
+impl Timer for ManualClock {
+    type Sleep = ManualSleep;
+
+    fn sleep_until(&self, deadline: Instant) -> Self::Sleep {
+        let id = {
+            let mut state = self.state.lock();
+            state.next_id += 1;
+            state.next_id
+        };
+
+        ManualSleep {
+            state: self.state.clone(),
+            deadline,
+            id,
+        }
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.state.lock().now
+    }
+
+    fn system_now(&self) -> SystemTime {
+        self.state.lock().system_now
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Sleep future of [`ManualClock`].
+#[derive(Debug)]
+pub struct ManualSleep {
+    /// Состояние часов
+    state: Arc<Mutex<ManualState>>,
+
+    /// До какого момента спим
+    deadline: Instant,
+
+    /// Идентификатор в списке ожидающих
+    id: u64,
+}
+
+impl Future for ManualSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock();
+
+        if state.now >= self.deadline {
+            state.sleepers.remove(&self.id);
+            Poll::Ready(())
+        } else {
+            // Регистрируем пробуждение, заменяя прошлое
+            state
+                .sleepers
+                .insert(self.id, (self.deadline, cx.waker().clone()));
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for ManualSleep {
+    fn drop(&mut self) {
+        self.state.lock().sleepers.remove(&self.id);
+    }
+}
@@ -0,0 +1,201 @@
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+    time::{Duration, Instant},
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Футуры, ждущие таймера слота, вместе с владельцем.
+/// Владелец полит свой таймер с этим пробуждением, поэтому срабатывание таймера
+/// будит сразу всех, даже если задачу владельца никто не полит.
+#[derive(Default)]
+struct SlotWakers {
+    /// Сработал ли уже таймер и кого будить, пока не сработал
+    state: Mutex<(bool, Vec<(u64, Waker)>)>,
+}
+
+impl SlotWakers {
+    /// Добавляем или обновляем пробуждение футуры, пока таймер не сработал
+    fn register(&self, future_id: u64, cx: &Context<'_>) -> Poll<()> {
+        let mut state = self.state.lock();
+
+        let (fired, wakers) = &mut *state;
+        if *fired {
+            return Poll::Ready(());
+        }
+
+        match wakers.iter_mut().find(|(id, _)| *id == future_id) {
+            Some((_, waker)) => {
+                if !waker.will_wake(cx.waker()) {
+                    *waker = cx.waker().clone();
+                }
+            }
+            None => wakers.push((future_id, cx.waker().clone())),
+        }
+
+        Poll::Pending
+    }
+
+    /// Убираем пробуждение футуры
+    fn remove(&self, future_id: u64) {
+        self.state.lock().1.retain(|(id, _)| *id != future_id);
+    }
+
+    /// Будим всех ждущих
+    fn wake_all(&self) {
+        let wakers = std::mem::take(&mut self.state.lock().1);
+
+        // Будим уже без блокировки
+        for (_, waker) in wakers {
+            waker.wake();
+        }
+    }
+}
+
+impl Wake for SlotWakers {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.state.lock().0 = true;
+        self.wake_all();
+    }
+}
+
+/// Слот времени, на который уже заведен таймер
+struct Slot {
+    /// Футура, которая спит на таймере слота
+    owner: u64,
+
+    /// Футуры, ждущие пробуждения по таймеру слота
+    wakers: Arc<SlotWakers>,
+}
+
+/// Объединение пробуждений футур извлечения.
+/// Сроки итемов округляются вверх до шага, и на каждый слот заводится один таймер:
+/// его срабатывание будит все футуры, ждущие итемы того же слота.
+pub(super) struct Coalescer {
+    /// Шаг округления сроков
+    resolution: Duration,
+
+    /// Начало отсчета слотов
+    epoch: Instant,
+
+    /// Слоты с заведенными таймерами по их времени
+    slots: Mutex<HashMap<Instant, Slot>>,
+}
+
+impl Coalescer {
+    pub(super) fn new(resolution: Duration, epoch: Instant) -> Coalescer {
+        assert!(!resolution.is_zero(), "Resolution should be positive");
+
+        Coalescer {
+            resolution,
+            epoch,
+            slots: Mutex::default(),
+        }
+    }
+
+    /// Округляем срок вверх до границы слота, прошедшие до начала отсчета не трогаем
+    pub(super) fn round(&self, deadline: Instant) -> Instant {
+        let Some(since) = deadline.checked_duration_since(self.epoch) else {
+            return deadline;
+        };
+
+        let resolution = self.resolution.as_nanos();
+        let slots = since.as_nanos().div_ceil(resolution);
+
+        match u64::try_from(slots * resolution) {
+            Ok(nanos) => self.epoch + Duration::from_nanos(nanos),
+            Err(_) => deadline,
+        }
+    }
+
+    /// Становимся владельцем слота, если таймер на него еще не заведен или уже сработал,
+    /// иначе ждем вместе с владельцем. Возвращает, владеем ли мы слотом.
+    pub(super) fn join(&self, deadline: Instant, future_id: u64, cx: &Context<'_>) -> bool {
+        let mut slots = self.slots.lock();
+
+        if let Some(slot) = slots.get(&deadline) {
+            if slot.owner == future_id {
+                return true;
+            }
+            if slot.wakers.register(future_id, cx).is_pending() {
+                return false;
+            }
+        }
+
+        let wakers = Arc::new(SlotWakers::default());
+        let _ = wakers.register(future_id, cx);
+
+        slots.insert(
+            deadline,
+            Slot {
+                owner: future_id,
+                wakers,
+            },
+        );
+
+        true
+    }
+
+    /// Пробуждение, с которым владелец полит таймер слота, чтобы таймер будил всех ждущих.
+    /// Пробуждение самого владельца при этом обновляется.
+    pub(super) fn owner_waker(
+        &self,
+        deadline: Instant,
+        future_id: u64,
+        cx: &Context<'_>,
+    ) -> Option<Waker> {
+        let slots = self.slots.lock();
+
+        let slot = slots
+            .get(&deadline)
+            .filter(|slot| slot.owner == future_id)?;
+        let _ = slot.wakers.register(future_id, cx);
+
+        Some(Waker::from(slot.wakers.clone()))
+    }
+
+    /// Проверяем, сработал ли таймер слота или ушел его владелец
+    pub(super) fn poll_follow(
+        &self,
+        deadline: Instant,
+        future_id: u64,
+        cx: &Context<'_>,
+    ) -> Poll<()> {
+        let slots = self.slots.lock();
+
+        match slots.get(&deadline) {
+            Some(slot) if slot.owner != future_id => slot.wakers.register(future_id, cx),
+            _ => Poll::Ready(()),
+        }
+    }
+
+    /// Уходим из слота. Если уходит владелец - таймер сработал или футуру отменили,
+    /// будим всех ждущих: готовые итемы разберут, а кто-то из них заведет таймер заново.
+    pub(super) fn leave(&self, deadline: Instant, future_id: u64) {
+        let mut slots = self.slots.lock();
+
+        let Some(slot) = slots.get(&deadline) else {
+            return;
+        };
+
+        if slot.owner != future_id {
+            slot.wakers.remove(future_id);
+            return;
+        }
+
+        let slot = slots.remove(&deadline).expect("Slot should exist");
+
+        // Будим уже без блокировки
+        drop(slots);
+
+        slot.wakers.remove(future_id);
+        slot.wakers.wake_all();
+    }
+}
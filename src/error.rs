@@ -0,0 +1,72 @@
+use std::{fmt, io};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Error of pushing an item into the queue, the item is returned back
+/// unless the overflow policy dropped it.
+#[non_exhaustive]
+pub enum PushError<T> {
+    /// Item is heavier than the whole `max_weight` budget of the queue,
+    /// so it would never fit.
+    TooHeavy(T),
+
+    /// Queue is full and its overflow policy is [`Reject`](crate::OverflowPolicy::Reject).
+    Full(T),
+
+    /// Cron schedule of the item has no upcoming occurrences.
+    NoOccurrence(T),
+
+    /// Queue is full and its overflow policy [`DropNewest`](crate::OverflowPolicy::DropNewest)
+    /// dropped the item, it was passed to the `on_evicted` callback if configured.
+    Dropped,
+
+    /// Persistent store of the queue failed to save the item.
+    Store(T, io::Error),
+}
+
+impl<T> PushError<T> {
+    /// Returns the item which was not pushed, `None` if it was dropped.
+    pub fn into_inner(self) -> Option<T> {
+        match self {
+            PushError::TooHeavy(item)
+            | PushError::Full(item)
+            | PushError::NoOccurrence(item)
+            | PushError::Store(item, _) => Some(item),
+            PushError::Dropped => None,
+        }
+    }
+}
+
+// Без требования `T: Debug`, как у ошибок каналов
+impl<T> fmt::Debug for PushError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PushError::TooHeavy(_) => f.write_str("TooHeavy(..)"),
+            PushError::Full(_) => f.write_str("Full(..)"),
+            PushError::NoOccurrence(_) => f.write_str("NoOccurrence(..)"),
+            PushError::Dropped => f.write_str("Dropped"),
+            PushError::Store(_, err) => write!(f, "Store(.., {err:?})"),
+        }
+    }
+}
+
+impl<T> fmt::Display for PushError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PushError::TooHeavy(_) => f.write_str("item is heavier than the queue max weight"),
+            PushError::Full(_) => f.write_str("queue is full"),
+            PushError::NoOccurrence(_) => f.write_str("schedule has no upcoming occurrences"),
+            PushError::Dropped => f.write_str("item is dropped by the overflow policy"),
+            PushError::Store(_, err) => write!(f, "store failed to save the item: {err}"),
+        }
+    }
+}
+
+impl<T> std::error::Error for PushError<T> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PushError::Store(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
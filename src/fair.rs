@@ -0,0 +1,48 @@
+use parking_lot::Mutex;
+use std::{
+    collections::BTreeMap,
+    task::{Context, Waker},
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Очередность футур извлечения для честного режима.
+/// Работать с очередью может только футура с наименьшим идентификатором,
+/// остальные ждут, пока она не получит итем или не будет отменена.
+#[derive(Default)]
+pub(super) struct FairTurns {
+    /// Ожидающие футуры по идентификаторам
+    waiters: Mutex<BTreeMap<u64, Waker>>,
+}
+
+impl FairTurns {
+    /// Встаем в очередь, если еще не стоим, и проверяем, наша ли очередь
+    pub(super) fn poll_turn(&self, future_id: u64, cx: &mut Context<'_>) -> bool {
+        let mut waiters = self.waiters.lock();
+
+        // Обновляем пробуждение, футуру могли перекинуть на другую задачу
+        waiters.insert(future_id, cx.waker().clone());
+
+        waiters.keys().next() == Some(&future_id)
+    }
+
+    /// Уходим из очереди, следующая по порядку футура будится
+    pub(super) fn leave(&self, future_id: u64) {
+        let mut waiters = self.waiters.lock();
+
+        let was_first = waiters.keys().next() == Some(&future_id);
+
+        if waiters.remove(&future_id).is_none() || !was_first {
+            return;
+        }
+
+        let next = waiters.values().next().cloned();
+
+        // Будим уже без блокировки
+        drop(waiters);
+
+        if let Some(next) = next {
+            next.wake();
+        }
+    }
+}
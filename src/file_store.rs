@@ -0,0 +1,243 @@
+use crate::store::{DelayStore, StoredItem};
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, de::IgnoredAny, Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Запись в логе
+#[derive(Serialize, Deserialize)]
+enum Record<T> {
+    /// Добавление итема или обновление времени
+    Insert {
+        key: u64,
+        deadline: SystemTime,
+        item: T,
+    },
+
+    /// Удаление итема
+    Remove { key: u64 },
+}
+
+/// Состояние открытого лога
+struct State {
+    /// Куда дописываем новые записи
+    writer: BufWriter<File>,
+
+    /// Время срабатывания живых итемов по ключам
+    deadlines: BTreeMap<u64, SystemTime>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// File backed append-only [`DelayStore`]. Every change is appended
+/// to the file as a JSON line, items are replayed from the log on load.
+/// Use [`FileStore::compact`] to drop records of removed items.
+///
+/// Every record is written and flushed to the OS synchronously, but not synced to disk:
+/// items survive a process crash, not a power loss. A record torn by a crash
+/// is dropped on [`open`](FileStore::open).
+pub struct FileStore<T> {
+    /// Путь к файлу лога
+    path: PathBuf,
+
+    /// Открытый лог
+    state: Mutex<State>,
+
+    /// Тип итемов
+    item: PhantomData<fn(T) -> T>,
+}
+
+impl<T> FileStore<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Opens log at `path`, creating it if it doesn't exist.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<FileStore<T>> {
+        let path = path.as_ref().to_path_buf();
+
+        // Для индекса сами итемы не нужны, поэтому их пропускаем
+        let (records, valid_len) = read_log::<IgnoredAny>(&path)?;
+
+        let mut deadlines = BTreeMap::new();
+        for record in records {
+            match record {
+                Record::Insert { key, deadline, .. } => {
+                    deadlines.insert(key, deadline);
+                }
+                Record::Remove { key } => {
+                    deadlines.remove(&key);
+                }
+            }
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        // Оборванную при падении запись отрезаем, иначе следующая допишется прямо к ней
+        if file.metadata()?.len() > valid_len {
+            file.set_len(valid_len)?;
+        }
+
+        let writer = BufWriter::new(file);
+
+        Ok(FileStore {
+            path,
+            state: Mutex::new(State { writer, deadlines }),
+            item: PhantomData,
+        })
+    }
+
+    /// Rewrites the log keeping only items which are still stored.
+    pub fn compact(&self) -> io::Result<()> {
+        let mut state = self.state.lock();
+
+        state.writer.flush()?;
+
+        // Пишем живые итемы во временный файл, а затем атомарно подменяем им лог
+        let tmp_path = self.path.with_extension("compact");
+        let mut tmp = BufWriter::new(File::create(&tmp_path)?);
+        for stored in replay::<T>(&self.path)? {
+            write_record(
+                &mut tmp,
+                &Record::Insert {
+                    key: stored.key,
+                    deadline: stored.deadline,
+                    item: stored.item,
+                },
+            )?;
+        }
+        tmp.into_inner()
+            .map_err(|err| err.into_error())?
+            .sync_all()?;
+
+        fs::rename(&tmp_path, &self.path)?;
+
+        state.writer = BufWriter::new(OpenOptions::new().append(true).open(&self.path)?);
+
+        Ok(())
+    }
+}
+
+impl<T> DelayStore<T> for FileStore<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn insert(&self, key: u64, item: &T, deadline: SystemTime) -> io::Result<()> {
+        let mut state = self.state.lock();
+
+        write_record(
+            &mut state.writer,
+            &Record::Insert {
+                key,
+                deadline,
+                item,
+            },
+        )?;
+
+        state.deadlines.insert(key, deadline);
+
+        Ok(())
+    }
+
+    fn remove(&self, key: u64) -> io::Result<()> {
+        let mut state = self.state.lock();
+
+        // Такого итема и так нет, писать нечего
+        if state.deadlines.remove(&key).is_none() {
+            return Ok(());
+        }
+
+        write_record::<()>(&mut state.writer, &Record::Remove { key })
+    }
+
+    fn load_all(&self) -> io::Result<Vec<StoredItem<T>>> {
+        self.state.lock().writer.flush()?;
+
+        replay(&self.path)
+    }
+
+    fn next_due(&self) -> Option<SystemTime> {
+        self.state.lock().deadlines.values().min().copied()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Дописываем запись в лог и сразу отдаем ОС, без синхронизации с диском
+fn write_record<T: Serialize>(writer: &mut BufWriter<File>, record: &Record<T>) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, record)?;
+    writer.write_all(b"\n")?;
+    writer.flush()
+}
+
+/// Читаем все записи лога и длину его целой части.
+/// Запись целая, только если за ней есть перевод строки,
+/// оборванную при падении последнюю строку пропускаем.
+fn read_log<T: DeserializeOwned>(path: &Path) -> io::Result<(Vec<Record<T>>, u64)> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok((Vec::new(), 0)),
+        Err(err) => return Err(err),
+    };
+
+    let mut reader = BufReader::new(file);
+    let mut records = Vec::new();
+    let mut valid_len = 0;
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+
+        // Конец лога или оборванная последняя строка
+        if read == 0 || line.last() != Some(&b'\n') {
+            break;
+        }
+
+        match serde_json::from_slice(&line) {
+            Ok(record) => records.push(record),
+            Err(_) if reader.fill_buf()?.is_empty() => break,
+            Err(err) => return Err(err.into()),
+        }
+
+        valid_len += read as u64;
+    }
+
+    Ok((records, valid_len))
+}
+
+/// Восстанавливаем живые итемы из лога в порядке добавления
+fn replay<T: DeserializeOwned>(path: &Path) -> io::Result<Vec<StoredItem<T>>> {
+    let mut items = BTreeMap::new();
+
+    for record in read_log::<T>(path)?.0 {
+        match record {
+            Record::Insert {
+                key,
+                deadline,
+                item,
+            } => {
+                items.insert(
+                    key,
+                    StoredItem {
+                        key,
+                        item,
+                        deadline,
+                    },
+                );
+            }
+            Record::Remove { key } => {
+                items.remove(&key);
+            }
+        }
+    }
+
+    Ok(items.into_values().collect())
+}
@@ -1,92 +1,218 @@
-use crate::{item::DelayItem, reserve::ReserveWaker, sleep::SleepLocal};
-use async_condvar_fair::{Baton, BatonExt, Condvar};
-use parking_lot::Mutex;
+use crate::{
+    clock::{Clock, DefaultClock},
+    error::PushError,
+    item::{to_instant, DelayItem, WALL_CLOCK_RECHECK},
+    lease::Lease,
+    overflow::OverflowPolicy,
+    popped::Popped,
+    queue::{DelayedQueue, Inner},
+    reserve::ReserveWaker,
+};
+use pin_project_lite::pin_project;
 use std::{
-    collections::VecDeque,
     future::Future,
     pin::Pin,
-    sync::{atomic::Ordering, Arc},
-    task::Poll,
-    time::Instant,
+    sync::atomic::Ordering,
+    task::{Context, Poll},
+    time::{Duration, Instant},
 };
 
 ////////////////////////////////////////////////////////////////////////////////
 
-type CondvarWaitFuture<'a> = dyn Future<Output = Option<Baton<'a>>> + Send + 'a;
+/// Таймаут видимости и функция клонирования итема
+type Visibility<T> = (Duration, fn(&T) -> T);
 
 ////////////////////////////////////////////////////////////////////////////////
 
-/// Delayed queue future.
-// Футура проверки доступности нового итема
-pub struct DelayedPopFuture<'a, T> {
-    // /// Нотифаер для отправки уведомлений
-    pub(super) size_condvar: &'a Condvar,
-
-    // /// Нотифаер для отправки уведомлений
-    pub(super) reserve_condvar: &'a Condvar,
-
-    // /// Сама очередь непосредственно
-    pub(super) queue: &'a Mutex<VecDeque<DelayItem<T>>>,
+pin_project! {
+    /// Delayed queue future.
+    ///
+    /// The future is `!Unpin`: the timer sleep is stored inline to keep waiting allocation-free.
+    /// Pin it with [`std::pin::pin!`] or `tokio::pin!` to poll it by `&mut` reference,
+    /// for example in `select!`:
+    ///
+    /// ```rust
+    /// # use tokio_delayed_queue::DelayedQueue;
+    /// # use std::time::Duration;
+    /// # tokio_test::block_on(async {
+    ///
+    /// let queue = DelayedQueue::new(16);
+    /// queue.push(1, Duration::from_millis(20)).await.unwrap();
+    ///
+    /// let mut pop = std::pin::pin!(queue.pop());
+    /// loop {
+    ///     tokio::select! {
+    ///         item = &mut pop => {
+    ///             assert_eq!(item, 1);
+    ///             break;
+    ///         }
+    ///         _ = tokio::time::sleep(Duration::from_millis(5)) => {}
+    ///     }
+    /// }
+    ///
+    /// # });
+    /// ```
+    // Футура проверки доступности нового итема.
+    // Ожидание не требует аллокаций: ждем в общем списке очереди,
+    // а футура сна хранится прямо внутри и пинируется вместе с нами.
+    pub struct DelayedPopFuture<'a, T, C: Clock = DefaultClock> {
+        // Общие данные очереди: сама очередь и нотифаеры
+        pub(super) inner: &'a Inner<T, C>,
+
+        // Ждем ли уведомления о новом свободном итеме
+        pub(super) waiting: bool,
+
+        // Футура ожидания времени зарезервированного итема
+        #[pin]
+        pub(super) sleep_future: Option<C::Sleep>,
+
+        // Отслеживание отмены ожидания футуры
+        pub(super) reserve_waker: Option<ReserveWaker<'a>>,
+
+        // Какой это у нас идентификатор футуры, который зарезервировал итем
+        pub(super) future_id: u64,
+
+        // Таймаут видимости и функция клонирования для извлечения с подтверждением
+        pub(super) visibility: Option<Visibility<T>>,
+
+        // Дошла ли очередь до этой футуры в честном режиме
+        pub(super) has_turn: bool,
+
+        // Слот объединенного пробуждения и владеем ли мы его таймером
+        pub(super) coalesced: Option<(Instant, bool)>,
+    }
 
-    // /// Футура возможного ожидания новых итемов
-    pub(super) condvar_future: Option<Pin<Box<CondvarWaitFuture<'a>>>>,
+    impl<'a, T, C: Clock> PinnedDrop for DelayedPopFuture<'a, T, C> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
 
-    // /// Футура ожидания нового итема
-    // TODO: Здесь можно было бы не аллоцировать, а как есть футуру использовать,
-    // но тогда пришлось бы использовать pin_project или unsafe
-    // для того, чтобы обеспечить гарантию того, что футура у нас приклеена к стеку.
-    pub(super) sleep_future: Option<SleepLocal>,
+            // Непрочитанное уведомление отдаем другой футуре
+            if *this.waiting {
+                this.inner.pop_waiters.cancel(*this.future_id);
+            }
 
-    // /// Отслеживание отмены ожидания футуры
-    pub(super) reserve_waker: Option<ReserveWaker<'a>>,
+            // Ушедший владелец таймера будит остальных футур слота
+            if let (Some((deadline, _)), Some(coalescer)) =
+                (*this.coalesced, this.inner.coalescer.as_ref())
+            {
+                coalescer.leave(deadline, *this.future_id);
+            }
 
-    // /// Какой это у нас идентификатор футуры, который зарезервировал итем
-    pub(super) future_id: u64,
+            // Отмененная футура не должна задерживать остальных в честном режиме
+            if let Some(fair) = this.inner.fair.as_ref() {
+                fair.leave(*this.future_id);
+            }
+        }
+    }
 }
 
-// Помечаем явно, что у нас эта самая футура не привязана никак к расположению своему.
-// TODO: Но вроде бы это и так у нас будет автоматически?
-impl<'a, T> Unpin for DelayedPopFuture<'a, T> {}
-
-// Явно помечаем дополнительно, что у нас IoHandle не является запинированным если S: Source.
-// Видимо, это нужно чтобы указать Unpin только для определенных типов S.
-// impl<'a, T> Unpin for DelayedPop<'a, T> where T: Unpin {}
-
-impl<'a, T: Send> Future for DelayedPopFuture<'a, T> {
+impl<'a, T, C: Clock> Future for DelayedPopFuture<'a, T, C> {
     type Output = T;
 
     fn poll(
-        mut self: std::pin::Pin<&mut Self>,
+        self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Self::Output> {
+        self.poll_item(cx).map(|popped| popped.item)
+    }
+}
+
+impl<'a, T, C: Clock> DelayedPopFuture<'a, T, C> {
+    // Основная логика извлечения, отдаем итем целиком вместе с его метаданными
+    pub(super) fn poll_item(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<DelayItem<T>> {
+        let mut this = self.project();
+
+        // Для удобства, ссылка не привязана к self
+        let inner = *this.inner;
+        let future_id = *this.future_id;
+
         'main_loop: loop {
-            // Уже была создана футура для ожидания ранее?
-            if let Some(condvar_future) = self.as_mut().condvar_future.as_mut() {
-                // Полим один раз для проверки, регистрируется пробуждение
-                match condvar_future.as_mut().poll(cx) {
+            // Ждали уведомления о новом свободном итеме?
+            if *this.waiting {
+                match inner.pop_waiters.poll_notified(future_id, cx) {
                     // Еще не готово
                     Poll::Pending => {
                         // Ждем очередного пробуждения
                         return Poll::Pending;
                     }
-                    // Что-то оказалось готово, продолжаем
-                    Poll::Ready(res) => {
-                        // Если ожидание сработало, то обрабатываем событие
-                        res.dispose();
+                    // Уведомление пришло, идем на новую итерацию проверки
+                    Poll::Ready(()) => {
+                        *this.waiting = false;
 
-                        // Уничтожаем футуру - она отработала
-                        self.as_mut().condvar_future.take();
+                        continue 'main_loop;
+                    }
+                }
+            }
+
+            // Итем, на котором спим, убрали из очереди или появился итем раньше него -
+            // перепроверяем очередь, не дожидаясь срока
+            if this.sleep_future.is_some() || matches!(*this.coalesced, Some((_, false))) {
+                let kicked = this.reserve_waker.as_ref().is_some_and(|reserve_waker| {
+                    inner
+                        .reservations
+                        .poll_kicked(reserve_waker.reservation, cx)
+                });
+
+                if kicked {
+                    this.sleep_future.set(None);
+
+                    // В честном режиме новый итем выбираем только в свою очередь,
+                    // поэтому резервирование отпускаем и встаем в нее заново
+                    if inner.fair.is_some() {
+                        this.reserve_waker.take();
+                    }
+
+                    // Ушедший владелец таймера будит остальных футур слота
+                    if let (Some((deadline, _)), Some(coalescer)) =
+                        (this.coalesced.take(), inner.coalescer.as_ref())
+                    {
+                        coalescer.leave(deadline, future_id);
+                    }
+
+                    continue 'main_loop;
+                }
+            }
+
+            // Ждем таймер слота вместе с его владельцем?
+            if let (Some((deadline, false)), Some(coalescer)) =
+                (*this.coalesced, inner.coalescer.as_ref())
+            {
+                match coalescer.poll_follow(deadline, future_id, cx) {
+                    // Еще не готово
+                    Poll::Pending => {
+                        // Ждем очередного пробуждения
+                        return Poll::Pending;
+                    }
+                    // Таймер сработал или владелец ушел, идем на новую итерацию проверки
+                    Poll::Ready(()) => {
+                        *this.coalesced = None;
 
-                        // Идем на новую итерацию проверки
                         continue 'main_loop;
                     }
                 }
             }
 
             // Уже была создана футура для ожидания ранее?
-            if let Some(sleep_future) = self.as_mut().sleep_future.as_mut() {
+            if let Some(sleep_future) = this.sleep_future.as_mut().as_pin_mut() {
+                // Владелец слота полит таймер с общим пробуждением слота,
+                // чтобы срабатывание разбудило всех ждущих без участия нашей задачи
+                let slot_waker = match (*this.coalesced, inner.coalescer.as_ref()) {
+                    (Some((deadline, true)), Some(coalescer)) => {
+                        coalescer.owner_waker(deadline, future_id, cx)
+                    }
+                    _ => None,
+                };
+
                 // Полим один раз для проверки, регистрируется пробуждение
-                match Pin::new(sleep_future).poll(cx) {
+                let poll = match slot_waker.as_ref() {
+                    Some(slot_waker) => sleep_future.poll(&mut Context::from_waker(slot_waker)),
+                    None => sleep_future.poll(cx),
+                };
+
+                match poll {
                     // Еще не готово
                     Poll::Pending => {
                         // Ждем очередного пробуждения
@@ -95,7 +221,14 @@ impl<'a, T: Send> Future for DelayedPopFuture<'a, T> {
                     // Что-то оказалось готово, продолжаем
                     Poll::Ready(_) => {
                         // Уничтожаем футуру - она отработала
-                        self.as_mut().sleep_future.take();
+                        this.sleep_future.set(None);
+
+                        // Будим всех, кто ждал этот же слот
+                        if let (Some((deadline, _)), Some(coalescer)) =
+                            (this.coalesced.take(), inner.coalescer.as_ref())
+                        {
+                            coalescer.leave(deadline, future_id);
+                        }
 
                         // Идем на новую итерацию проверки
                         continue 'main_loop;
@@ -103,51 +236,169 @@ impl<'a, T: Send> Future for DelayedPopFuture<'a, T> {
                 }
             }
 
+            // В честном режиме ждем, пока не обслужат все футуры, созданные раньше нас.
+            // Футура, спящая на своем зарезервированном итеме, уже обслужена:
+            // очередь она отпустила и может забрать только этот итем.
+            if let Some(fair) = inner.fair.as_ref() {
+                if !*this.has_turn && this.reserve_waker.is_none() {
+                    if !fair.poll_turn(future_id, cx) {
+                        return Poll::Pending;
+                    }
+                    *this.has_turn = true;
+                }
+            }
+
+            // Можно ли выбрать новый итем, а не только свой
+            let may_pick = inner.fair.is_none() || *this.has_turn;
+
             // Берем блокировку короткую для очереди
-            let mut lock = self.queue.lock();
+            let mut lock = inner.queue.lock();
+
+            // Наше текущее резервирование, если есть
+            let ours = this
+                .reserve_waker
+                .as_ref()
+                .map(|reserve_waker| reserve_waker.reservation);
+
+            // Берем первый итем, свободный или уже зарезервированный нами.
+            // Так каждая футура ждет свой итем и просыпается точно к его времени.
+            // Таблицу резервирований отпускаем до снятия нашего старого резервирования ниже.
+            let index = {
+                let reservations = inner.reservations.lock();
+                let index =
+                    lock.position(inner.clock.now(), |queue_item| match queue_item.reserved {
+                        Some(reservation) => {
+                            Some(reservation) == ours
+                                || (may_pick && !reservations.is_live(reservation))
+                        }
+                        None => may_pick,
+                    });
+
+                // Чужое резервирование итема сняли без извлечения - учитываем отмену один раз
+                if let Some(index) = index {
+                    let queue_item = lock.get_mut(index);
+                    if let Some(reservation) = queue_item.reserved {
+                        if Some(reservation) != ours && !reservations.is_live(reservation) {
+                            queue_item.cancellations += 1;
+                            queue_item.reserved = None;
+                        }
+                    }
+                }
+
+                index
+            };
+
+            // Футура переходит на другой итем - с прежнего резервирование снимем,
+            // иначе его посчитают отменой
+            let moved = ours
+                .filter(|&ours| index.is_some_and(|index| lock.get(index).reserved != Some(ours)));
 
             // Смотрим наличие итема
-            if let Some(front_val) = lock.front_mut() {
-                // Идентификатор футуры, которая зарезервировала этот итем
-                let reserved_id = front_val.reserved.load(Ordering::Acquire);
+            if let Some(index) = index {
+                let item_val = lock.get_mut(index);
 
-                // Данный итем кем-то другим зарезервирован уже, но не нами же?
-                if (reserved_id > 0) && (reserved_id != self.future_id) {
-                    // Создаем футуру ожидания
-                    let wait_future = self.reserve_condvar.wait_no_relock(lock);
+                // Повторения итема остановили, выкидываем его сразу же без ожидания
+                if item_val.is_stopped() {
+                    let stopped = lock.remove(index);
 
-                    // Раз блокировка каждый раз новая, то и футура для
-                    // просыпания тоже пусть будет новая каждый раз
-                    let prev = self.as_mut().condvar_future.replace(Box::pin(wait_future));
+                    // Перед уведомлением снимаем блокировку
+                    drop(lock);
 
-                    // Прошлой футуры быть не должно здесь
-                    assert!(prev.is_none(), "Previous condvar wait should not exist");
+                    inner.store_remove(stopped.key);
+
+                    // Говорим, что освободилось новое место
+                    inner.notify_space();
 
-                    // Полить будем на следующей итерации
                     continue 'main_loop;
                 }
+
+                // Итем протухнет раньше, чем его можно будет отдать, выкидываем сразу же
+                if item_val.is_expired(inner.clock.now()) {
+                    let expired = lock.remove(index);
+
+                    // Перед уведомлением снимаем блокировку
+                    drop(lock);
+
+                    inner.store_remove(expired.key);
+
+                    inner.expired.fetch_add(1, Ordering::Relaxed);
+
+                    // Говорим, что освободилось новое место
+                    inner.notify_space();
+
+                    if let Some(on_expired) = inner.on_expired.as_ref() {
+                        on_expired(expired.item);
+                    }
+
+                    continue 'main_loop;
+                }
+
+                // Часы могли сдвинуться, пересчитываем монотонное время по исходному
+                let rechecked = item_val.system_time.filter(|_| inner.recheck_wall_clock);
+                if let Some(system_time) = rechecked {
+                    item_val.pop_time =
+                        to_instant(system_time, inner.clock.now(), inner.clock.system_now());
+                }
+
+                // Срок с учетом округления до слота
+                let deadline = inner.rounded(item_val.pop_time);
+
                 // Футуры еще не было создано для ожидания,
                 // но время еще не настало
                 // для отдачи
-                else if front_val.pop_time > Instant::now() {
-                    // Создаем тогда футуру для пробуждения
-                    let prev = self.as_mut().sleep_future.replace(SleepLocal::new(
-                        tokio::time::sleep_until(front_val.pop_time.into()),
-                    ));
+                if deadline > inner.clock.now() {
+                    // Прошлой футуры быть не должно здесь
+                    assert!(this.sleep_future.is_none(), "Sleep future should not exist");
+
+                    // Скачок часов вперед заметим только при пробуждении,
+                    // поэтому такие итемы ждем отдельно и не дольше периода перепроверки
+                    let coalescer = inner.coalescer.as_ref().filter(|_| rechecked.is_none());
+                    let wake_at = if rechecked.is_some() {
+                        deadline.min(inner.clock.now() + WALL_CLOCK_RECHECK)
+                    } else {
+                        deadline
+                    };
+
+                    // На слот уже заведен таймер - ждем вместе с его владельцем,
+                    // иначе создаем футуру для пробуждения
+                    match coalescer {
+                        Some(coalescer) if !coalescer.join(deadline, future_id, cx) => {
+                            *this.coalesced = Some((deadline, false));
+                        }
+                        coalescer => {
+                            *this.coalesced = coalescer.map(|_| (deadline, true));
+                            this.sleep_future
+                                .set(Some(inner.clock.sleep_until(wake_at)));
+                        }
+                    }
 
-                    assert!(prev.is_none(), "Sleep future should not exist");
+                    // Прошлое резервирование снимаем до нового,
+                    // иначе оно сбросит резервирование того же итема
+                    this.reserve_waker.take();
 
-                    // Выставляем флаг резервирования текущим футуры
-                    front_val.reserved.store(self.future_id, Ordering::Release);
+                    // Создаем waker для отслеживания отмены футуры,
+                    // через резервирование нас разбудят, если итем уберут из очереди
+                    let reserve_waker =
+                        ReserveWaker::new(&inner.pop_waiters, &inner.reservations, deadline, cx);
 
-                    // Создаем waker для отслеживания отмены футуры
-                    self.reserve_waker = Some(ReserveWaker {
-                        condvar: self.reserve_condvar,
-                        item_reserved_future: Arc::downgrade(&front_val.reserved),
-                    });
+                    // Выставляем резервирование текущей футуры
+                    item_val.reserved = Some(reserve_waker.reservation);
+
+                    if let Some(moved) = moved {
+                        lock.unreserve(moved);
+                    }
+
+                    *this.reserve_waker = Some(reserve_waker);
 
                     drop(lock);
 
+                    // Спящая футура не должна задерживать остальных в честном режиме
+                    if let Some(fair) = inner.fair.as_ref() {
+                        if std::mem::take(this.has_turn) {
+                            fair.leave(future_id);
+                        }
+                    }
+
                     // Запустим футуру ожидания на новой итерации
                     continue 'main_loop;
                 }
@@ -155,39 +406,565 @@ impl<'a, T: Send> Future for DelayedPopFuture<'a, T> {
                 else {
                     // Теперь можем смело извлечиь итем, он там точно есть - проверка выше,
                     // поэтому можно unwrap
-                    let item = lock.pop_front().expect("First item should exist").item;
+                    let mut popped = lock.remove(index);
+                    let popped_key = popped.key;
+
+                    if let Some(moved) = moved {
+                        lock.unreserve(moved);
+                    }
+
+                    // Периодический итем сразу же взводим заново в конец очереди
+                    let next_item = popped.recurrence.take().and_then(|recurrence| {
+                        let now = inner.clock.now();
+
+                        let next_pop_time = recurrence.next_pop_time(popped.pop_time, now)?;
+
+                        let mut next_item =
+                            DelayItem::new(recurrence.clone_item(&popped.item), next_pop_time, now);
+                        next_item.key = inner.next_key();
+                        next_item.recurrence = Some(recurrence);
+
+                        Some(next_item)
+                    });
+
+                    // Итем слишком сильно опоздал, отдаем его в dead-letter вместо получателя
+                    let dead_letter = inner
+                        .lateness
+                        .as_ref()
+                        .filter(|(max_lateness, _)| {
+                            inner
+                                .clock
+                                .now()
+                                .saturating_duration_since(inner.rounded(popped.pop_time))
+                                > *max_lateness
+                        })
+                        .map(|(_, dead_letter)| dead_letter);
+
+                    // Для извлечения с подтверждением оставляем в очереди копию,
+                    // которая снова станет доступна по истечении таймаута видимости
+                    let leased = match *this.visibility {
+                        Some((visibility_timeout, clone)) if dead_letter.is_none() => {
+                            let now = inner.clock.now();
+
+                            let mut leased =
+                                DelayItem::new(clone(&popped.item), now + visibility_timeout, now);
+                            leased.key = inner.next_key();
+                            popped.key = leased.key;
+
+                            Some(leased)
+                        }
+                        _ => None,
+                    };
+
+                    // Место в очереди освобождается, только если ничего не вернули обратно
+                    let copies = [next_item, leased];
+                    let slot_freed = copies.iter().all(Option::is_none);
+
+                    // Копии сохраняем без блокировки, пока их еще не видно в очереди,
+                    // а места под них держим занятыми
+                    if inner.store.is_some() {
+                        let held = copies.iter().flatten().count();
+                        lock.hold(held);
+                        drop(lock);
+
+                        inner.store_remove(popped_key);
+                        for copy in copies.iter().flatten() {
+                            inner.store_insert_logged(copy);
+                        }
+
+                        lock = inner.queue.lock();
+                        lock.release(held);
+                    }
+
+                    for copy in copies.into_iter().flatten() {
+                        lock.push(copy);
+                    }
 
                     // Перед уведомлением снимаем блокировку
                     drop(lock);
 
-                    // Говорим, что освободилось новое место
-                    self.size_condvar.notify_one();
+                    // Говорим, что освободилось новое место,
+                    // либо что в очереди появился новый свободный итем
+                    if slot_freed {
+                        inner.notify_space();
+                    } else {
+                        inner.notify_item();
+                    }
+
+                    if let Some(dead_letter) = dead_letter {
+                        inner.diverted.fetch_add(1, Ordering::Relaxed);
 
-                    // Дополнительно можно было бы еще уведомить об этом через reserve_condvar,
+                        dead_letter(popped.item);
+
+                        continue 'main_loop;
+                    }
+
+                    // Дополнительно можно было бы еще уведомить об этом через pop_waiters,
                     // но это будет сделано автоматически при уничтожении футуры.
-                    // self.reserve_condvar.notify_one();
+                    // inner.pop_waiters.notify_one();
+
+                    // Очередь переходит к следующей футуре
+                    if let Some(fair) = inner.fair.as_ref() {
+                        fair.leave(future_id);
+                    }
 
                     // Итем готов
-                    return Poll::Ready(item);
+                    return Poll::Ready(popped);
                 }
+            } else if !may_pick {
+                // Наш итем пропал, снова встаем в очередь за новым
+                drop(lock);
+
+                this.reserve_waker.take();
+
+                continue 'main_loop;
             } else {
-                // Создаем футуру ожидания
-                let wait_future = self.size_condvar.wait_no_relock(lock);
+                // Очередь пуста или все итемы разобраны другими футурами -
+                // ждем нового итема или отмены резервирования.
+                // Регистрируемся под блокировкой, чтобы не пропустить уведомление.
+                inner.pop_waiters.register(future_id, cx);
 
-                // Раз блокировка каждый раз новая, то и футура для
-                // просыпания тоже пусть будет новая каждый раз
-                let prev = self.as_mut().condvar_future.replace(Box::pin(wait_future));
+                drop(lock);
 
-                // Прошлой футуры быть не должно здесь
-                assert!(prev.is_none(), "Previous condvar wait should not exist");
+                *this.waiting = true;
 
-                // Полить будем на следующей итерации
+                // Проверим уведомление на следующей итерации
                 continue 'main_loop;
             }
         }
     }
 }
 
+////////////////////////////////////////////////////////////////////////////////
+
+pin_project! {
+    /// Delayed queue future of pop with acknowledgement.
+    ///
+    /// `!Unpin` like [`DelayedPopFuture`], pin it to poll it by `&mut` reference.
+    pub struct DelayedAckFuture<'a, T, C: Clock = DefaultClock> {
+        // Обычная футура извлечения в режиме подтверждения
+        #[pin]
+        pub(super) pop: DelayedPopFuture<'a, T, C>,
+
+        // Очередь, в которую вернется итем без подтверждения
+        pub(super) queue: &'a DelayedQueue<T, C>,
+    }
+}
+
+impl<'a, T, C: Clock> Future for DelayedAckFuture<'a, T, C> {
+    type Output = Lease<T, C>;
+
+    fn poll(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        let this = self.project();
+        let queue = *this.queue;
+
+        this.pop
+            .poll_item(cx)
+            .map(|popped| Lease::new(queue.clone(), popped.key, popped.item))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pin_project! {
+    /// Delayed queue future of pop with item metadata.
+    ///
+    /// `!Unpin` like [`DelayedPopFuture`], pin it to poll it by `&mut` reference.
+    pub struct DelayedMetaFuture<'a, T, C: Clock = DefaultClock> {
+        // Обычная футура извлечения
+        #[pin]
+        pub(super) pop: DelayedPopFuture<'a, T, C>,
+    }
+}
+
+impl<'a, T, C: Clock> Future for DelayedMetaFuture<'a, T, C> {
+    type Output = Popped<T>;
+
+    fn poll(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        let this = self.project();
+        let inner = *this.pop.as_ref().project_ref().inner;
+
+        this.pop.poll_item(cx).map(|popped| Popped {
+            item: popped.item,
+            key: popped.key,
+            pop_time: popped.pop_time,
+            enqueued_at: popped.enqueued_at,
+            popped_at: inner.clock.now(),
+            cancellations: popped.cancellations,
+        })
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pin_project! {
+    /// Delayed queue future returned by [`DelayedQueue::ready`].
+    ///
+    /// Resolves when the item which would be popped first becomes due.
+    /// The item is neither reserved nor removed, so another consumer may pop it first.
+    ///
+    /// `!Unpin` like [`DelayedPopFuture`], pin it to poll it by `&mut` reference.
+    // Футура ожидания готовности первого итема.
+    // Спим до его срока и параллельно ждем изменений очереди:
+    // новый итем может оказаться раньше, а извлеченный - смениться следующим.
+    pub struct DelayedReadyFuture<'a, T, C: Clock = DefaultClock> {
+        // Общие данные очереди
+        pub(super) inner: &'a Inner<T, C>,
+
+        // Ждем ли уведомления об изменении очереди
+        pub(super) waiting: bool,
+
+        // Футура ожидания срока первого итема
+        #[pin]
+        pub(super) sleep_future: Option<C::Sleep>,
+
+        // Срок, на который заведена футура ожидания
+        pub(super) deadline: Option<Instant>,
+
+        // Идентификатор футуры в списке ожидания
+        pub(super) future_id: u64,
+    }
+
+    impl<'a, T, C: Clock> PinnedDrop for DelayedReadyFuture<'a, T, C> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+
+            // Непрочитанное уведомление отдаем другой футуре
+            if *this.waiting {
+                this.inner.ready_waiters.cancel(*this.future_id);
+            }
+        }
+    }
+}
+
+impl<'a, T, C: Clock> Future for DelayedReadyFuture<'a, T, C> {
+    type Output = ();
+
+    fn poll(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        let mut this = self.project();
+
+        // Для удобства, ссылка не привязана к self
+        let inner = *this.inner;
+        let future_id = *this.future_id;
+
+        loop {
+            // Ждали уведомления об изменении очереди?
+            if *this.waiting {
+                if let Poll::Ready(()) = inner.ready_waiters.poll_notified(future_id, cx) {
+                    *this.waiting = false;
+                }
+            }
+
+            // Очередь не менялась - проверяем, не настал ли срок
+            if *this.waiting {
+                let Some(sleep_future) = this.sleep_future.as_mut().as_pin_mut() else {
+                    return Poll::Pending;
+                };
+
+                if sleep_future.poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+
+                // Уничтожаем футуру - она отработала
+                this.sleep_future.set(None);
+                *this.deadline = None;
+
+                // Перепроверим очередь на новой итерации
+                inner.ready_waiters.cancel(future_id);
+                *this.waiting = false;
+            }
+
+            // Берем блокировку короткую для очереди
+            let mut lock = inner.queue.lock();
+
+            let now = inner.clock.now();
+
+            // Срок итема, который извлечется первым, резервирования не важны,
+            // и когда проснуться для его проверки
+            let deadline = lock.position(now, |_| true).map(|index| {
+                let queue_item = lock.get(index);
+
+                // Часы могли сдвинуться, пересчитываем монотонное время по исходному.
+                // Скачок вперед заметим, только если будем просыпаться периодически.
+                match queue_item.system_time {
+                    Some(system_time) if inner.recheck_wall_clock => {
+                        let pop_time = to_instant(system_time, now, inner.clock.system_now());
+                        let deadline = inner.rounded(pop_time);
+                        (deadline, deadline.min(now + WALL_CLOCK_RECHECK))
+                    }
+                    _ => {
+                        let deadline = inner.rounded(queue_item.pop_time);
+                        (deadline, deadline)
+                    }
+                }
+            });
+
+            // Итем готов
+            if deadline.is_some_and(|(deadline, _)| deadline <= now) {
+                return Poll::Ready(());
+            }
+
+            // Регистрируемся под блокировкой, чтобы не пропустить уведомление
+            inner.ready_waiters.register(future_id, cx);
+
+            drop(lock);
+
+            *this.waiting = true;
+
+            // Срок сменился - заводим футуру ожидания заново
+            if *this.deadline != deadline.map(|(deadline, _)| deadline) {
+                *this.deadline = deadline.map(|(deadline, _)| deadline);
+                this.sleep_future
+                    .set(deadline.map(|(_, wake_at)| inner.clock.sleep_until(wake_at)));
+            }
+
+            // Проверим уведомление и срок на следующей итерации
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Delayed queue push future returned by [`DelayedQueue::push_cancellable`].
+///
+/// Resolves to the key of the pushed item, the same as [`Popped::key`](crate::Popped::key)
+/// when it is popped.
+///
+/// Cancellation safe: the item is moved into the queue only when the future completes,
+/// so a pending future never leaves the queue half-updated or the item pushed twice.
+/// Dropping the future drops the item with it, use [`into_inner`](Self::into_inner)
+/// to get the item back from a pending future, for example after `select!` picked another branch.
+///
+/// ```rust
+/// # use tokio_delayed_queue::DelayedQueue;
+/// # use std::time::Duration;
+/// # tokio_test::block_on(async {
+///
+/// let queue = DelayedQueue::new(1);
+/// queue.push(1, Duration::ZERO).await.unwrap();
+///
+/// // Queue is full, push waits
+/// let mut push = queue.push_cancellable(2, Duration::ZERO);
+/// tokio::select! {
+///     _ = &mut push => unreachable!(),
+///     _ = tokio::time::sleep(Duration::from_millis(10)) => {}
+/// }
+///
+/// assert_eq!(push.into_inner(), Some(2));
+/// assert_eq!(queue.len(), 1);
+///
+/// # });
+/// ```
+// Футура ждет места в том же списке ожидания, что и футуры извлечения ждут итемов,
+// поэтому не держит блокировку очереди через await и не требует пиннинга.
+pub struct DelayedPushFuture<'a, T, C: Clock = DefaultClock> {
+    // Общие данные очереди
+    pub(super) inner: &'a Inner<T, C>,
+
+    // Итем, пока он еще не попал в очередь
+    pub(super) item: Option<DelayItem<T>>,
+
+    // Вес итема, считается один раз
+    pub(super) weight: Option<usize>,
+
+    // Сохранен ли уже итем в хранилище
+    pub(super) stored: bool,
+
+    // Ждем ли уведомления об освободившемся месте
+    pub(super) waiting: bool,
+
+    // Идентификатор футуры в списке ожидания
+    pub(super) future_id: u64,
+}
+
+impl<'a, T, C: Clock> DelayedPushFuture<'a, T, C> {
+    /// Returns the item if it has not been pushed yet, cancelling the push.
+    pub fn into_inner(mut self) -> Option<T> {
+        let queue_item = self.item.take()?;
+        self.forget_stored(&queue_item);
+
+        Some(queue_item.item)
+    }
+
+    // Итем в очередь так и не попал - убираем его из хранилища, вызывается без блокировки
+    fn forget_stored(&self, queue_item: &DelayItem<T>) {
+        if self.stored {
+            self.inner.store_remove(queue_item.key);
+        }
+    }
+}
+
+// Итем никогда не пиннится, он только перемещается в очередь
+impl<'a, T, C: Clock> Unpin for DelayedPushFuture<'a, T, C> {}
+
+impl<'a, T, C: Clock> Future for DelayedPushFuture<'a, T, C> {
+    type Output = Result<u64, PushError<T>>;
+
+    fn poll(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        let this = self.get_mut();
+
+        // Для удобства, ссылка не привязана к self
+        let inner = this.inner;
+
+        loop {
+            // Ждали уведомления об освободившемся месте?
+            if this.waiting {
+                match inner.push_waiters.poll_notified(this.future_id, cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(()) => this.waiting = false,
+                }
+            }
+
+            // Берем блокировку короткую над очередью
+            let mut lock = inner.queue.lock();
+
+            let queue_item = this
+                .item
+                .as_ref()
+                .expect("Push future should not be polled after completion");
+
+            let weight = *this
+                .weight
+                .get_or_insert_with(|| lock.weigh(&queue_item.item));
+
+            // Такой итем не влезет, даже если очередь опустеет
+            if weight > inner.max_weight {
+                let queue_item = this.item.take().expect("Item should exist");
+                return Poll::Ready(Err(PushError::TooHeavy(queue_item.item)));
+            }
+
+            // Итем сохраняем в хранилище до того, как он станет виден в очереди,
+            // и без ее блокировки, чтобы медленная запись не держала остальных
+            if !this.stored && inner.store.is_some() {
+                drop(lock);
+
+                if let Err(err) = inner.store_insert(queue_item) {
+                    let queue_item = this.item.take().expect("Item should exist");
+                    return Poll::Ready(Err(PushError::Store(queue_item.item, err)));
+                }
+
+                this.stored = true;
+
+                continue;
+            }
+
+            // Сколько мест и веса надо освободить под новый итем
+            let need_len =
+                (lock.occupied() + 1).saturating_sub(inner.max_size.load(Ordering::Relaxed));
+            let need_weight = lock
+                .weight()
+                .saturating_add(weight)
+                .saturating_sub(inner.max_weight);
+
+            // Выбираем вытесняемые итемы по политике переполнения целиком заранее,
+            // чтобы не вытеснить часть из них впустую
+            let victims = match inner.overflow_policy {
+                _ if need_len == 0 && need_weight == 0 => Some(Vec::new()),
+                // Очередь больше уменьшенной емкости - ждем при любой политике,
+                // уменьшение емкости не должно выкидывать итемы
+                _ if need_len > 1 => {
+                    inner.push_waiters.register(this.future_id, cx);
+
+                    drop(lock);
+
+                    this.waiting = true;
+
+                    continue;
+                }
+                OverflowPolicy::Wait => {
+                    // Места нет - ждем его, регистрируясь под блокировкой,
+                    // чтобы не пропустить уведомление
+                    inner.push_waiters.register(this.future_id, cx);
+
+                    drop(lock);
+
+                    this.waiting = true;
+
+                    // Проверим уведомление на следующей итерации
+                    continue;
+                }
+                OverflowPolicy::Reject => {
+                    drop(lock);
+
+                    let queue_item = this.item.take().expect("Item should exist");
+                    this.forget_stored(&queue_item);
+
+                    return Poll::Ready(Err(PushError::Full(queue_item.item)));
+                }
+                OverflowPolicy::DropNewest => None,
+                OverflowPolicy::DropOldest => lock.oldest_victims(need_len, need_weight),
+                OverflowPolicy::EvictLatestDeadline => {
+                    lock.latest_victims(queue_item.pop_time, need_len, need_weight)
+                }
+            };
+
+            // Вытеснением места не освободить - выкидываем новый итем, очередь не трогаем
+            let Some(victims) = victims else {
+                drop(lock);
+
+                let queue_item = this.item.take().expect("Item should exist");
+                this.forget_stored(&queue_item);
+                inner.evicted(queue_item.item);
+
+                return Poll::Ready(Err(PushError::Dropped));
+            };
+
+            // Вытесненные итемы отдаем уже без блокировки
+            let evicted = lock.remove_all(victims);
+
+            let mut queue_item = this.item.take().expect("Item should exist");
+
+            // Добавляем итем
+            let key = queue_item.key;
+            let pop_time = queue_item.pop_time;
+            let by_deadline = lock.by_deadline();
+            queue_item.weight = weight;
+            lock.push_weighed(queue_item);
+
+            // Снимаем блокировку
+            drop(lock);
+
+            // Теперь уведомляем, что итем стал доступен новый
+            inner.notify_pushed(pop_time, by_deadline);
+
+            for victim in evicted {
+                inner.store_remove(victim.key);
+                inner.evicted_item(victim);
+            }
+
+            return Poll::Ready(Ok(key));
+        }
+    }
+}
+
+impl<'a, T, C: Clock> Drop for DelayedPushFuture<'a, T, C> {
+    fn drop(&mut self) {
+        // Непрочитанное уведомление отдаем другой футуре
+        if self.waiting {
+            self.inner.push_waiters.cancel(self.future_id);
+        }
+
+        // Брошенный итем не должен восстановиться из хранилища
+        if let Some(queue_item) = self.item.take() {
+            self.forget_stored(&queue_item);
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 // // Если нотифаера не было еще - регистрируем его.
 // let notified = unsafe {
 //     // Создаем ссылкe только на notify, без self
@@ -225,7 +1002,7 @@ impl<'a, T: Send> Future for DelayedPopFuture<'a, T> {
 }; */
 
 // Альтернативный вариант
-// let pinned = unsafe { self.as_mut().map_unchecked_mut(|v| &mut v.notified) };
+// let pinned = unsafe { self.map_unchecked_mut(|v| &mut v.notified) };
 
 // Проверяем разово готовность данного нотифаера, может быть уже стало что-то готово?
 // При пробуждении мы проверим работу еще раз
@@ -1,19 +1,98 @@
-use std::{
-    sync::{atomic::AtomicU64, Arc},
-    time::Instant,
-};
+use crate::{recurrence::Recurrence, reserve::Reservation};
+use std::time::{Duration, Instant, SystemTime};
 
 ////////////////////////////////////////////////////////////////////////////////
 
 /// Итем в очереди
 pub(super) struct DelayItem<T> {
+    /// Уникальный ключ итема внутри очереди
+    pub(super) key: u64,
+
     /// В какой момент времени надо отдать будет итем
     pub(super) pop_time: Instant,
 
+    /// Когда итем добавили в очередь
+    pub(super) enqueued_at: Instant,
+
     /// Сам итем непосредственно
     pub(super) item: T,
 
     /// Какая именно футура зарезервировала этот итем.
-    /// Используется именно Arc, так как итемы у нас под блокировкой
-    pub(super) reserved: Arc<AtomicU64>,
+    /// Снятое резервирование остается здесь, но уже не действует по таблице
+    pub(super) reserved: Option<Reservation>,
+
+    /// Сколько раз резервирование итема снимали без его извлечения
+    pub(super) cancellations: u32,
+
+    /// Параметры повторения, если итем периодический
+    pub(super) recurrence: Option<Recurrence<T>>,
+
+    /// После какого момента итем уже никому не нужен
+    pub(super) expires_at: Option<Instant>,
+
+    /// Исходное время срабатывания по часам, если итем добавляли так
+    pub(super) system_time: Option<SystemTime>,
+
+    /// Вес итема, посчитанный при добавлении в хранилище
+    pub(super) weight: usize,
+}
+
+impl<T> DelayItem<T> {
+    /// Новый итем без резервирования, ключ выставляется при добавлении в очередь
+    pub(super) fn new(item: T, pop_time: Instant, now: Instant) -> DelayItem<T> {
+        DelayItem {
+            key: 0,
+            pop_time,
+            enqueued_at: now,
+            item,
+            reserved: None,
+            cancellations: 0,
+            recurrence: None,
+            expires_at: None,
+            system_time: None,
+            weight: 0,
+        }
+    }
+
+    /// Повторения итема были остановлены через хендл
+    pub(super) fn is_stopped(&self) -> bool {
+        self.recurrence
+            .as_ref()
+            .map(|recurrence| recurrence.is_stopped())
+            .unwrap_or(false)
+    }
+
+    /// Итем протухнет раньше, чем его можно будет отдать
+    pub(super) fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at
+            .map(|expires_at| expires_at <= self.pop_time.max(now))
+            .unwrap_or(false)
+    }
+
+    /// Время срабатывания по часам, исходное если оно есть
+    pub(super) fn wall_deadline(&self, now: Instant, system_now: SystemTime) -> SystemTime {
+        self.system_time
+            .unwrap_or_else(|| to_system_time(self.pop_time, now, system_now))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Как часто спящие футуры перепроверяют время по часам, чтобы заметить скачок вперед
+pub(super) const WALL_CLOCK_RECHECK: Duration = Duration::from_secs(1);
+
+/// Переводим монотонное время во время по часам относительно текущего момента,
+/// оба текущих момента берем из одних часов очереди
+pub(super) fn to_system_time(instant: Instant, now: Instant, system_now: SystemTime) -> SystemTime {
+    if instant >= now {
+        system_now + (instant - now)
+    } else {
+        system_now - (now - instant)
+    }
+}
+
+/// Переводим время по часам в монотонное относительно текущего момента,
+/// прошедшее время считаем текущим моментом
+pub(super) fn to_instant(time: SystemTime, now: Instant, system_now: SystemTime) -> Instant {
+    now + time.duration_since(system_now).unwrap_or_default()
 }
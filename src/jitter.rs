@@ -0,0 +1,40 @@
+use crate::rng::{duration_between, RandomSource};
+use std::time::Duration;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Randomization of push delays for spreading items pushed with the same delay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Jitter {
+    /// Random delay in `[0, delay]`.
+    Full,
+
+    /// Random delay in `[delay / 2, delay]`.
+    Equal,
+
+    /// Random delay in `[delay - percent%, delay + percent%]`.
+    Bounded {
+        /// Maximum deviation in percents of the delay.
+        percent: u32,
+    },
+}
+
+impl Jitter {
+    /// Применяем случайный разброс к задержке
+    pub(super) fn apply(&self, delay: Duration, rng: &mut dyn RandomSource) -> Duration {
+        match *self {
+            Jitter::Full => duration_between(rng, Duration::ZERO, delay),
+            Jitter::Equal => duration_between(rng, delay / 2, delay),
+            Jitter::Bounded { percent } => {
+                // Максимальное отклонение в обе стороны
+                let spread = delay.saturating_mul(percent) / 100;
+
+                duration_between(
+                    rng,
+                    delay.saturating_sub(spread),
+                    delay.saturating_add(spread),
+                )
+            }
+        }
+    }
+}
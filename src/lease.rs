@@ -0,0 +1,97 @@
+use crate::{
+    clock::{Clock, DefaultClock},
+    queue::DelayedQueue,
+};
+use std::{
+    ops::{Deref, DerefMut},
+    time::Duration,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Item popped with acknowledgement.
+///
+/// Until [`Lease::ack`] is called a copy of the item stays in the queue and
+/// becomes poppable again once the visibility timeout expires.
+/// Dropping the lease without acknowledgement makes the item poppable immediately.
+///
+/// ```rust
+/// # use tokio_delayed_queue::DelayedQueue;
+/// # use std::time::Duration;
+/// # tokio_test::block_on(async {
+///
+/// let queue = DelayedQueue::new(16);
+/// queue.push(1, Duration::ZERO).await.unwrap();
+///
+/// // Not acknowledged, returns back to the queue
+/// let lease = queue.pop_ack().await;
+/// drop(lease);
+///
+/// let lease = queue.pop_ack().await;
+/// assert_eq!(*lease, 1);
+/// assert!(lease.ack());
+///
+/// # });
+/// ```
+pub struct Lease<T, C: Clock = DefaultClock> {
+    /// Очередь, в которой лежит копия итема
+    queue: DelayedQueue<T, C>,
+
+    /// Ключ копии итема в очереди
+    key: u64,
+
+    /// Сам итем
+    item: T,
+
+    /// Было ли уже подтверждение или возврат
+    settled: bool,
+}
+
+impl<T, C: Clock> Lease<T, C> {
+    // Новая аренда итема
+    pub(super) fn new(queue: DelayedQueue<T, C>, key: u64, item: T) -> Lease<T, C> {
+        Lease {
+            queue,
+            key,
+            item,
+            settled: false,
+        }
+    }
+
+    /// Acknowledges processing, the item is removed from the queue.
+    /// Returns `false` if the visibility timeout already expired and the item was popped again.
+    pub fn ack(mut self) -> bool {
+        self.settled = true;
+        self.queue.remove_by_key(self.key)
+    }
+
+    /// Returns the item back to the queue tail, it becomes poppable after `delay`.
+    /// Returns `false` if the visibility timeout already expired and the item was popped again.
+    pub fn nack(mut self, delay: Duration) -> bool {
+        self.settled = true;
+        self.queue.requeue_by_key(self.key, delay)
+    }
+}
+
+impl<T, C: Clock> Deref for Lease<T, C> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.item
+    }
+}
+
+impl<T, C: Clock> DerefMut for Lease<T, C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.item
+    }
+}
+
+impl<T, C: Clock> Drop for Lease<T, C> {
+    fn drop(&mut self) {
+        // Не было подтверждения - итем сразу же снова доступен
+        if !self.settled {
+            self.queue.requeue_by_key(self.key, Duration::ZERO);
+        }
+    }
+}
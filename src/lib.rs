@@ -1,69 +1,150 @@
 // #![doc = include_str!("../README.md")]
 
-/// # Tokio delayed queue
-/// 
-/// Asyncronous delayed queue for Tokio runtime. 
-/// 
-/// # Features
-/// 
-/// - multi-consume
-/// - multi-produce
-/// - fixed queue size
-/// - atomic pop with pop-future cancelation
-///
-/// ```rust
-/// # use tokio_delayed_queue::DelayedQueue;
-/// # use std::time::Duration;
-/// # tokio_test::block_on(async {
-/// 
-/// let queue = DelayedQueue::new(16);
-/// 
-/// // Push
-/// queue.push(1, Duration::from_secs(1)).await;
-/// queue.push(1, Duration::from_secs(2)).await;
-/// 
-/// // Pop
-/// let v = queue.pop().await;
-/// assert_eq!(v, 1);
-/// 
-/// // Other future
-/// let join = tokio::spawn({
-///     let queue = queue.clone();
-///     async move {
-///         // Cancelled 1
-///         let dropped_future = queue.pop();
-///         drop(dropped_future);
-/// 
-///         // Cancelled 2
-///         let dropped_future = queue.pop();
-///         drop(dropped_future);
-/// 
-///         // Pop
-///         let v = queue.pop().await;
-///         assert_eq!(v, 1);
-/// 
-///         // Pop
-///         let v = queue.pop().await;
-///         assert_eq!(v, 1);
-///     }
-/// });
-/// 
-/// // Push
-/// queue.push(1, Duration::from_secs(2)).await;
-/// 
-/// join.await.unwrap();
-/// 
-/// # });
-/// ```
+//! # Tokio delayed queue
+//!
+//! Asyncronous delayed queue for Tokio runtime.
+//!
+//! # Features
+//!
+//! - multi-consume
+//! - multi-produce
+//! - fixed queue size
+//! - atomic pop with pop-future cancelation
+//! - periodic items
+//! - cron schedules (`cron` feature)
+//! - retry queue with backoff
+//! - delay jitter
+//! - pop with acknowledgement and visibility timeout
+//! - dead letter for items popped too late
+//! - per-item TTL
+//! - persistent stores, file backed with `persistence` feature
+//! - snapshot and restore with `serde` feature
+//! - wall-clock deadlines
+//! - pluggable clock with manual clock for simulation tests
+//! - runtime-agnostic timers: Tokio by default, `async-io` and `thread-timer` features
+//! - fair mode serving consumers in FIFO order
+//! - sharded queue for high producer contention
+//! - hierarchical timing wheel backend for millions of pending items
+//! - deadline rounding with coalesced wakeups
+//! - memory-bounded queue by item weight
+//! - overflow policies: wait, reject or drop items
+//! - capacity resizing at runtime
+//! - cancellation-safe push future for `select!`, reporting the key of the pushed item
+//! - waiting for the next due item without popping it
+//! - pop with item metadata: deadline, enqueue and pop instants, cancellations
+//!
+//! ```rust
+//! # use tokio_delayed_queue::DelayedQueue;
+//! # use std::time::Duration;
+//! # tokio_test::block_on(async {
+//!
+//! let queue = DelayedQueue::new(16);
+//!
+//! // Push
+//! queue.push(1, Duration::from_secs(1)).await.unwrap();
+//! queue.push(1, Duration::from_secs(2)).await.unwrap();
+//!
+//! // Pop
+//! let v = queue.pop().await;
+//! assert_eq!(v, 1);
+//!
+//! // Other future
+//! let join = tokio::spawn({
+//!     let queue = queue.clone();
+//!     async move {
+//!         // Cancelled 1
+//!         let dropped_future = queue.pop();
+//!         drop(dropped_future);
+//!
+//!         // Cancelled 2
+//!         let dropped_future = queue.pop();
+//!         drop(dropped_future);
+//!
+//!         // Pop
+//!         let v = queue.pop().await;
+//!         assert_eq!(v, 1);
+//!
+//!         // Pop
+//!         let v = queue.pop().await;
+//!         assert_eq!(v, 1);
+//!     }
+//! });
+//!
+//! // Push
+//! queue.push(1, Duration::from_secs(2)).await.unwrap();
+//!
+//! join.await.unwrap();
+//!
+//! # });
+//! ```
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
+mod builder;
+mod clock;
+mod coalesce;
+mod error;
+mod fair;
+#[cfg(feature = "persistence")]
+mod file_store;
 mod future;
 mod item;
+mod jitter;
+mod lease;
+mod overflow;
+mod popped;
 mod queue;
+mod recurrence;
 mod reserve;
-mod sleep;
+mod retry;
+mod rng;
+mod sharded;
+#[cfg(feature = "serde")]
+mod snapshot;
+mod storage;
+mod store;
+#[cfg(feature = "thread-timer")]
+mod thread_timer;
+mod waiters;
+mod wheel;
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
-pub use self::{future::DelayedPopFuture, queue::DelayedQueue};
+pub use self::{
+    builder::DelayedQueueBuilder,
+    clock::{Clock, DefaultClock, ManualClock, ManualSleep, Timer},
+    error::PushError,
+    future::{
+        DelayedAckFuture, DelayedMetaFuture, DelayedPopFuture, DelayedPushFuture,
+        DelayedReadyFuture,
+    },
+    jitter::Jitter,
+    lease::Lease,
+    overflow::OverflowPolicy,
+    popped::Popped,
+    queue::DelayedQueue,
+    recurrence::{MissedTickBehavior, RecurrenceHandle},
+    retry::{Backoff, RetryItem, RetryQueue},
+    rng::{RandomSource, SplitMix64},
+    sharded::{ShardedDelayedQueue, ShardedPopFuture},
+    storage::Backend,
+    store::{DelayStore, MemoryStore, StoredItem},
+};
+
+#[cfg(feature = "tokio")]
+pub use clock::TokioClock;
+
+#[cfg(feature = "async-io")]
+pub use clock::{AsyncIoClock, AsyncIoSleep};
+
+#[cfg(feature = "thread-timer")]
+pub use thread_timer::{ThreadClock, ThreadSleep};
+
+#[cfg(feature = "persistence")]
+pub use file_store::FileStore;
+
+#[cfg(feature = "serde")]
+pub use snapshot::{QueueSnapshot, SnapshotItem};
+
+#[cfg(feature = "cron")]
+pub use cron::Schedule;
@@ -0,0 +1,27 @@
+////////////////////////////////////////////////////////////////////////////////
+
+/// What push does when the queue is full by size or weight.
+/// Evicted and dropped items are counted and passed to the `on_evicted` callback if configured.
+/// While the queue holds more items than a capacity shrunk by
+/// [`set_capacity`](crate::DelayedQueue::set_capacity), push waits under every policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Wait until enough space is freed.
+    #[default]
+    Wait,
+
+    /// Return the new item back with [`PushError::Full`](crate::PushError::Full).
+    Reject,
+
+    /// Drop the new item, push fails with [`PushError::Dropped`](crate::PushError::Dropped).
+    DropNewest,
+
+    /// Evict the items which stay in the queue longest until the new one fits.
+    /// Items of the timing wheel backend are scanned to find them.
+    DropOldest,
+
+    /// Evict the items with the latest deadlines until the new one fits,
+    /// the new item is dropped like with `DropNewest` if evicting the items with later deadlines
+    /// would not make room. Items are scanned to find them.
+    EvictLatestDeadline,
+}
@@ -0,0 +1,42 @@
+use std::time::{Duration, Instant};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Item popped with its metadata by [`DelayedQueue::pop_with_meta`](crate::DelayedQueue::pop_with_meta).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Popped<T> {
+    /// The item itself.
+    pub item: T,
+
+    /// Unique key of the item in the queue.
+    pub key: u64,
+
+    /// Scheduled time when the item became poppable.
+    pub pop_time: Instant,
+
+    /// Time when the item was pushed into the queue.
+    pub enqueued_at: Instant,
+
+    /// Time when the item was actually popped.
+    pub popped_at: Instant,
+
+    /// How many times a pop future reserved the item and was cancelled before popping it.
+    pub cancellations: u32,
+}
+
+impl<T> Popped<T> {
+    /// Time the item spent in the queue.
+    pub fn queued_for(&self) -> Duration {
+        self.popped_at.saturating_duration_since(self.enqueued_at)
+    }
+
+    /// How late the item was popped after its scheduled time.
+    pub fn lateness(&self) -> Duration {
+        self.popped_at.saturating_duration_since(self.pop_time)
+    }
+
+    /// Returns the item dropping the metadata.
+    pub fn into_inner(self) -> T {
+        self.item
+    }
+}
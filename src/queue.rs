@@ -12,36 +12,219 @@
 
 ////////////////////////////////////////////////////////////////////////////////
 
-use crate::{future::DelayedPopFuture, item::DelayItem};
-use async_condvar_fair::{BatonExt, Condvar};
+#[cfg(feature = "cron")]
+use crate::recurrence::cron_next_pop_time;
+use crate::{
+    builder::DelayedQueueBuilder,
+    clock::{Clock, DefaultClock},
+    coalesce::Coalescer,
+    error::PushError,
+    fair::FairTurns,
+    future::{
+        DelayedAckFuture, DelayedMetaFuture, DelayedPopFuture, DelayedPushFuture,
+        DelayedReadyFuture,
+    },
+    item::{to_instant, DelayItem},
+    jitter::Jitter,
+    overflow::OverflowPolicy,
+    recurrence::{MissedTickBehavior, Recurrence, RecurrenceHandle},
+    reserve::Reservations,
+    rng::RandomSource,
+    storage::Storage,
+    store::{DelayStore, StoredItem},
+    waiters::WaitList,
+};
 use parking_lot::Mutex;
 use std::{
-    collections::VecDeque,
+    io,
     sync::{
-        atomic::{AtomicU64, Ordering},
+        atomic::{AtomicU64, AtomicUsize, Ordering},
         Arc,
     },
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime},
 };
 
 ////////////////////////////////////////////////////////////////////////////////
 
+/// Колбек для итемов, которые убираются из очереди без извлечения
+pub(super) type ItemCallback<T> = Box<dyn Fn(T) + Send + Sync>;
+
 /// Структура данных, которую шарим между потоков
-struct Inner<T> {
-    /// Максимальный размер очереди
-    max_size: usize,
+pub(super) struct Inner<T, C> {
+    /// Максимальный размер очереди, меняется на ходу
+    pub(super) max_size: AtomicUsize,
+
+    /// Максимальный суммарный вес итемов
+    pub(super) max_weight: usize,
+
+    /// Что делаем при переполнении
+    pub(super) overflow_policy: OverflowPolicy,
+
+    /// Куда отдаем вытесненные и выкинутые при переполнении итемы
+    pub(super) on_evicted: Option<ItemCallback<T>>,
+
+    /// Сколько итемов вытеснено и выкинуто при переполнении
+    pub(super) dropped: AtomicU64,
 
     /// Очередь с синхронной блокировкой
-    queue: Mutex<VecDeque<DelayItem<T>>>,
+    pub(super) queue: Mutex<Storage<T>>,
 
-    /// Асинхронный condvar для оповещения об изменениях
-    size_condvar: Condvar,
+    /// Футуры добавления, ждущие свободного места
+    pub(super) push_waiters: WaitList,
 
-    /// Асинхронный condvar для оповещения об изменениях
-    reserve_condvar: Condvar,
+    /// Футуры извлечения, ждущие нового свободного итема
+    pub(super) pop_waiters: WaitList,
+
+    /// Футуры, ждущие готовности итема без его извлечения
+    pub(super) ready_waiters: WaitList,
+
+    /// Таблица резервирований итемов футурами
+    pub(super) reservations: Reservations,
 
     /// Счетчик футур ожидания
-    counter: AtomicU64,
+    pub(super) counter: AtomicU64,
+
+    /// Счетчик ключей итемов
+    pub(super) keys: AtomicU64,
+
+    /// Разброс задержек по умолчанию
+    pub(super) jitter: Option<Jitter>,
+
+    /// Генератор случайных чисел для разброса
+    pub(super) random_source: Mutex<Box<dyn RandomSource>>,
+
+    /// Таймаут видимости итемов, извлеченных с подтверждением
+    pub(super) visibility_timeout: Duration,
+
+    /// Максимальное опоздание и куда отдаем опоздавшие итемы
+    pub(super) lateness: Option<(Duration, ItemCallback<T>)>,
+
+    /// Сколько итемов ушло в dead-letter из-за опоздания
+    pub(super) diverted: AtomicU64,
+
+    /// Куда отдаем протухшие итемы
+    pub(super) on_expired: Option<ItemCallback<T>>,
+
+    /// Сколько итемов протухло
+    pub(super) expired: AtomicU64,
+
+    /// Долговременное хранилище итемов
+    pub(super) store: Option<Box<dyn DelayStore<T>>>,
+
+    /// Куда отдаем ошибки хранилища
+    pub(super) on_store_error: Option<Box<dyn Fn(io::Error) + Send + Sync>>,
+
+    /// Пересчитывать ли время итемов по часам при пробуждении
+    pub(super) recheck_wall_clock: bool,
+
+    /// Источник времени
+    pub(super) clock: C,
+
+    /// Очередность футур извлечения в честном режиме
+    pub(super) fair: Option<FairTurns>,
+
+    /// Объединение пробуждений по слотам времени
+    pub(super) coalescer: Option<Coalescer>,
+}
+
+impl<T, C: Clock> Inner<T, C> {
+    /// Новый уникальный ключ итема
+    pub(super) fn next_key(&self) -> u64 {
+        self.keys.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Сохраняем итем в хранилище. Вызывается без блокировки очереди,
+    /// пока итема в ней еще нет, поэтому удаление не может обогнать запись.
+    pub(super) fn store_insert(&self, queue_item: &DelayItem<T>) -> io::Result<()> {
+        match self.store.as_ref() {
+            Some(store) => store.insert(
+                queue_item.key,
+                &queue_item.item,
+                queue_item.wall_deadline(self.clock.now(), self.clock.system_now()),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Сохраняем итем, ошибку отдаем в колбек: ждущего ее отправителя нет
+    pub(super) fn store_insert_logged(&self, queue_item: &DelayItem<T>) {
+        let res = self.store_insert(queue_item);
+        self.handle_store_result(res);
+    }
+
+    /// Удаляем итем из хранилища, вызывается без блокировки очереди
+    pub(super) fn store_remove(&self, key: u64) {
+        if let Some(store) = self.store.as_ref() {
+            let res = store.remove(key);
+            self.handle_store_result(res);
+        }
+    }
+
+    /// Учитываем вытесненный при переполнении итем, вызывается без блокировки очереди
+    pub(super) fn evicted(&self, item: T) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(on_evicted) = self.on_evicted.as_ref() {
+            on_evicted(item);
+        }
+    }
+
+    /// Учитываем вытесненный из очереди итем, вызывается без блокировки очереди.
+    /// Зарезервировавшая его футура не должна проспать до его срока.
+    pub(super) fn evicted_item(&self, victim: DelayItem<T>) {
+        if let Some(reservation) = victim.reserved {
+            self.reservations.kick(reservation);
+        }
+
+        self.evicted(victim.item);
+    }
+
+    /// Уведомляем ждущих места отправителей, вызывается после снятия блокировки.
+    /// С учетом веса освободившегося места может не хватить первому из них,
+    /// поэтому будим всех, а лишние снова встанут в ожидание.
+    pub(super) fn notify_space(&self) {
+        if self.max_weight == usize::MAX {
+            self.push_waiters.notify_one();
+        } else {
+            self.push_waiters.notify_all();
+        }
+
+        // Первый итем мог смениться
+        self.ready_waiters.notify_all();
+    }
+
+    /// Уведомляем о новом свободном итеме, вызывается после снятия блокировки.
+    /// Извлекающую футуру будим одну, а ждущих готовности - всех, итем они не забирают.
+    pub(super) fn notify_item(&self) {
+        self.pop_waiters.notify_one();
+        self.ready_waiters.notify_all();
+    }
+
+    /// Уведомляем о добавленном итеме со сроком `pop_time`, вызывается после снятия блокировки.
+    /// Если свободной футуры нет, а итемы отдаются по срокам, будим футуру, спящую
+    /// дольше этого срока: иначе новый итем ждал бы, пока она дождется своего.
+    pub(super) fn notify_pushed(&self, pop_time: Instant, by_deadline: bool) {
+        if !self.pop_waiters.notify_one() && by_deadline {
+            self.reservations.kick_later(self.rounded(pop_time));
+        }
+
+        self.ready_waiters.notify_all();
+    }
+
+    /// Срок итема, округленный до слота, если задан шаг
+    pub(super) fn rounded(&self, pop_time: Instant) -> Instant {
+        match self.coalescer.as_ref() {
+            Some(coalescer) => coalescer.round(pop_time),
+            None => pop_time,
+        }
+    }
+
+    /// Отдаем ошибку хранилища, если кто-то ее ждет
+    fn handle_store_result(&self, res: io::Result<()>) {
+        if let (Err(err), Some(on_store_error)) = (res, self.on_store_error.as_ref()) {
+            on_store_error(err);
+        }
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -52,104 +235,483 @@ struct Inner<T> {
 /// # use tokio_delayed_queue::DelayedQueue;
 /// # use std::time::Duration;
 /// # tokio_test::block_on(async {
-/// 
+///
 /// // New queue
 /// let queue = DelayedQueue::new(16);
-/// 
+///
 /// // Push
-/// queue.push(1, Duration::from_secs(1)).await;
-/// 
+/// queue.push(1, Duration::from_secs(1)).await.unwrap();
+///
 /// // Pop
 /// let v = queue.pop().await;
 /// assert_eq!(v, 1);
-/// 
+///
 /// # });
 /// ```
 //
 // Используем `derive_where`,
 // чтобы не накладывать дополнительные условия на тип `T`.
 // #[derive_where(Clone)]
-pub struct DelayedQueue<T> {
-    inner: Arc<Inner<T>>,
+pub struct DelayedQueue<T, C = DefaultClock> {
+    pub(super) inner: Arc<Inner<T, C>>,
 }
 
 impl<T> DelayedQueue<T> {
-    /// Creates new queue with fixed preallocated capacity.
-    // Создание очереди сразу нужной емкости, аллоцируем сразу же нужный размер один раз.
+    /// Creates new queue with preallocated capacity.
     pub fn new(size: usize) -> DelayedQueue<T> {
+        DelayedQueue::builder(size).build()
+    }
+
+    /// Creates builder of queue with preallocated capacity and optional settings.
+    pub fn builder(size: usize) -> DelayedQueueBuilder<T> {
+        DelayedQueueBuilder::new(size)
+    }
+}
+
+impl<T, C: Clock> DelayedQueue<T, C> {
+    // Создание очереди сразу нужной емкости с выбранным хранилищем
+    pub(super) fn from_builder(builder: DelayedQueueBuilder<T, C>) -> DelayedQueue<T, C> {
+        let now = builder.clock.now();
+        let (max_weight, weigher) = match builder.weight {
+            Some((max_weight, weigher)) => (max_weight, Some(weigher)),
+            None => (usize::MAX, None),
+        };
+        let storage = Storage::new(builder.backend, builder.size, now, weigher);
+        let coalescer = builder
+            .resolution
+            .map(|resolution| Coalescer::new(resolution, now));
+
         DelayedQueue {
             inner: Arc::new(Inner {
-                max_size: size,
-                queue: Mutex::new(VecDeque::with_capacity(size)),
-                size_condvar: Condvar::new(),
-                reserve_condvar: Condvar::new(),
+                max_size: AtomicUsize::new(builder.size),
+                max_weight,
+                overflow_policy: builder.overflow_policy,
+                on_evicted: builder.on_evicted,
+                dropped: AtomicU64::new(0),
+                queue: Mutex::new(storage),
+                push_waiters: WaitList::default(),
+                pop_waiters: WaitList::default(),
+                ready_waiters: WaitList::default(),
+                reservations: Reservations::default(),
                 counter: AtomicU64::new(1),
+                keys: AtomicU64::new(1),
+                jitter: builder.jitter,
+                random_source: Mutex::new(builder.random_source),
+                visibility_timeout: builder.visibility_timeout,
+                lateness: builder.lateness,
+                diverted: AtomicU64::new(0),
+                on_expired: builder.on_expired,
+                expired: AtomicU64::new(0),
+                store: None,
+                on_store_error: builder.on_store_error,
+                recheck_wall_clock: builder.recheck_wall_clock,
+                clock: builder.clock,
+                fair: builder.fair.then(FairTurns::default),
+                coalescer,
             }),
         }
     }
 
-    /// Push new item.
+    // Создание очереди поверх хранилища с восстановлением сохраненных итемов
+    pub(super) fn from_store<S>(
+        builder: DelayedQueueBuilder<T, C>,
+        store: S,
+    ) -> io::Result<DelayedQueue<T, C>>
+    where
+        S: DelayStore<T> + 'static,
+    {
+        let stored = store.load_all()?;
+
+        let mut queue = DelayedQueue::from_builder(builder);
+
+        let inner = Arc::get_mut(&mut queue.inner).expect("Queue should not be shared yet");
+
+        let now = inner.clock.now();
+        let system_now = inner.clock.system_now();
+
+        let items = inner.queue.get_mut();
+        for StoredItem {
+            key,
+            item,
+            deadline,
+        } in stored
+        {
+            let mut queue_item = DelayItem::new(item, to_instant(deadline, now, system_now), now);
+            queue_item.key = key;
+            queue_item.system_time = Some(deadline);
+
+            items.push(queue_item);
+        }
+
+        // Новые ключи не должны пересекаться с восстановленными
+        let max_key = items
+            .iter()
+            .map(|queue_item| queue_item.key)
+            .max()
+            .unwrap_or(0);
+        *inner.keys.get_mut() = max_key + 1;
+
+        inner.store = Some(Box::new(store));
+
+        Ok(queue)
+    }
+
+    /// Push new item. Queue jitter is applied to the delay if configured.
+    ///
+    /// Returns the item back if it is heavier than the queue `max_weight` or if the queue
+    /// is full with [`OverflowPolicy::Reject`]. Other push methods fail the same way.
     // Добавляем новый итем с задержкой
-    #[allow(clippy::await_holding_lock)]
-    pub async fn push(&self, item: T, delay: Duration) {
-        // Для удобства
-        let this = self.inner.as_ref();
+    pub async fn push(&self, item: T, delay: Duration) -> Result<(), PushError<T>> {
+        let now = self.inner.clock.now();
+
+        // Когда будем пробуждаться
+        let pop_time = now + self.jittered(delay, self.inner.jitter);
+
+        self.push_item(DelayItem::new(item, pop_time, now)).await
+    }
+
+    /// Push new item like [`push`](Self::push), resolving to the key of the pushed item.
+    ///
+    /// The returned future is cancellation safe, see [`DelayedPushFuture`].
+    pub fn push_cancellable(&self, item: T, delay: Duration) -> DelayedPushFuture<'_, T, C> {
+        let now = self.inner.clock.now();
+
+        // Когда будем пробуждаться
+        let pop_time = now + self.jittered(delay, self.inner.jitter);
+
+        self.push_future(DelayItem::new(item, pop_time, now))
+    }
+
+    /// Push new item with jitter overriding the queue one.
+    pub async fn push_with_jitter(
+        &self,
+        item: T,
+        delay: Duration,
+        jitter: Jitter,
+    ) -> Result<(), PushError<T>> {
+        let now = self.inner.clock.now();
 
         // Когда будем пробуждаться
-        let pop_time = Instant::now() + delay;
-
-        // Пробуем получить блокировку над очередью
-        let mut lock = loop {
-            // Берем блокировку короткую над очередью
-            let lock = this.queue.lock();
-
-            // Проверяем размер очереди, если превышен
-            if lock.len() >= this.max_size {
-                // Тогда подождем возможности запихнуть новый элемент
-                this.size_condvar.wait_no_relock(lock).await.dispose();
-            } else {
-                // Все норм - отдаем дальше блокировку
-                break lock;
-            }
+        let pop_time = now + self.jittered(delay, Some(jitter));
+
+        self.push_item(DelayItem::new(item, pop_time, now)).await
+    }
+
+    // Применяем разброс к задержке, если он нужен
+    fn jittered(&self, delay: Duration, jitter: Option<Jitter>) -> Duration {
+        match jitter {
+            Some(jitter) => jitter.apply(delay, self.inner.random_source.lock().as_mut()),
+            None => delay,
+        }
+    }
+
+    /// Push new item poppable at wall-clock `time`. The deadline is converted
+    /// to monotonic time on push, use `recheck_wall_clock` builder setting
+    /// to follow wall clock jumps.
+    pub async fn push_at_system_time(&self, item: T, time: SystemTime) -> Result<(), PushError<T>> {
+        let now = self.inner.clock.now();
+        let system_now = self.inner.clock.system_now();
+
+        let queue_item = DelayItem {
+            system_time: Some(time),
+            ..DelayItem::new(item, to_instant(time, now, system_now), now)
         };
 
-        // Учитываем в том числе время нахождения в очереди,
-        // поэтому создаем фиксированнную точку пробуждения
-        // let pop_time = tokio::time::Instant::now() + delay;
+        self.push_item(queue_item).await
+    }
+
+    /// Push new item which is discarded if it can't be popped within `ttl` after the push.
+    /// Discarded items are passed to the `on_expired` callback if configured.
+    pub async fn push_with_ttl(
+        &self,
+        item: T,
+        delay: Duration,
+        ttl: Duration,
+    ) -> Result<(), PushError<T>> {
+        let now = self.inner.clock.now();
 
-        // Новый итем, сразу с футурой ожидания
         let queue_item = DelayItem {
-            pop_time,
-            item,
-            reserved: Arc::new(AtomicU64::new(0)),
+            expires_at: Some(now + ttl),
+            ..DelayItem::new(item, now + self.jittered(delay, self.inner.jitter), now)
         };
 
-        // Добавляем итем
-        lock.push_back(queue_item);
+        self.push_item(queue_item).await
+    }
 
-        // Снимаем блокировку
-        drop(lock);
+    /// Push periodic item. Every pop yields a clone of the item and re-arms
+    /// the next occurrence one `period` later according to `behavior`.
+    /// Re-armed occurrence is appended to the queue tail like a regular push.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero.
+    // Добавляем периодический итем
+    pub async fn push_periodic(
+        &self,
+        item: T,
+        initial_delay: Duration,
+        period: Duration,
+        behavior: MissedTickBehavior,
+    ) -> Result<RecurrenceHandle, PushError<T>>
+    where
+        T: Clone,
+    {
+        let now = self.inner.clock.now();
+
+        // Когда будем пробуждаться в первый раз
+        let pop_time = now + initial_delay;
+
+        let (recurrence, handle) = Recurrence::periodic(period, behavior);
+
+        let queue_item = DelayItem {
+            recurrence: Some(recurrence),
+            ..DelayItem::new(item, pop_time, now)
+        };
 
-        // Теперь уведомляем, что итем стал доступен новый
-        this.size_condvar.notify_one();
+        self.push_item(queue_item).await?;
+
+        Ok(handle)
+    }
+
+    /// Push item recurring by cron `schedule` evaluated in UTC.
+    /// Every pop yields a clone of the item and re-arms the next occurrence.
+    /// Returns the item back with [`PushError::NoOccurrence`] if the schedule
+    /// has no upcoming occurrences.
+    #[cfg(feature = "cron")]
+    pub async fn push_cron(
+        &self,
+        item: T,
+        schedule: cron::Schedule,
+    ) -> Result<RecurrenceHandle, PushError<T>>
+    where
+        T: Clone,
+    {
+        let now = self.inner.clock.now();
+
+        // Когда будем пробуждаться в первый раз
+        let Some(pop_time) = cron_next_pop_time(&schedule, now) else {
+            return Err(PushError::NoOccurrence(item));
+        };
+
+        let (recurrence, handle) = Recurrence::cron(schedule);
+
+        let queue_item = DelayItem {
+            recurrence: Some(recurrence),
+            ..DelayItem::new(item, pop_time, now)
+        };
+
+        self.push_item(queue_item).await?;
+
+        Ok(handle)
+    }
+
+    // Добавляем готовый итем в очередь
+    async fn push_item(&self, queue_item: DelayItem<T>) -> Result<(), PushError<T>> {
+        self.push_future(queue_item).await.map(|_| ())
+    }
+
+    // Футура добавления готового итема, освобождающая место по политике переполнения
+    fn push_future(&self, mut queue_item: DelayItem<T>) -> DelayedPushFuture<'_, T, C> {
+        // Выдаем итему уникальный ключ
+        queue_item.key = self.inner.next_key();
+
+        DelayedPushFuture {
+            inner: &self.inner,
+            item: Some(queue_item),
+            weight: None,
+            stored: false,
+            waiting: false,
+            future_id: self.inner.counter.fetch_add(1, Ordering::Release),
+        }
     }
 
     /// Atomically pop delayed item. It supports pop cancelation by returned future drop.
+    /// Each waiting future reserves the first item not reserved by others and sleeps
+    /// until its deadline, so concurrent consumers wait on distinct items in parallel.
     // Получение нового итема с нужной задержкой
-    pub fn pop(&self) -> DelayedPopFuture<T> {
+    pub fn pop(&self) -> DelayedPopFuture<'_, T, C> {
         DelayedPopFuture {
-            queue: &self.inner.queue,
-            size_condvar: &self.inner.size_condvar,
-            reserve_condvar: &self.inner.reserve_condvar,
-            condvar_future: None,
+            inner: &self.inner,
+            waiting: false,
             sleep_future: None,
             reserve_waker: None,
             future_id: self.inner.counter.fetch_add(1, Ordering::Release),
+            visibility: None,
+            has_turn: false,
+            coalesced: None,
+        }
+    }
+
+    /// Atomically pop delayed item with acknowledgement.
+    /// Until the returned [`Lease`](crate::Lease) is acknowledged a copy of the item stays in the queue
+    /// and becomes poppable again after the visibility timeout.
+    /// Dropping the lease without acknowledgement makes the item poppable immediately.
+    pub fn pop_ack(&self) -> DelayedAckFuture<'_, T, C>
+    where
+        T: Clone,
+    {
+        let mut pop = self.pop();
+        pop.visibility = Some((self.inner.visibility_timeout, T::clone));
+
+        DelayedAckFuture { pop, queue: self }
+    }
+
+    /// Atomically pop delayed item like [`pop`](Self::pop), returning it with its
+    /// scheduled time, enqueue and pop instants, key and reservation cancellations.
+    pub fn pop_with_meta(&self) -> DelayedMetaFuture<'_, T, C> {
+        DelayedMetaFuture { pop: self.pop() }
+    }
+
+    /// Wait until the item which would be popped first becomes due, without
+    /// reserving or removing it. Items pushed while waiting are taken into account.
+    pub fn ready(&self) -> DelayedReadyFuture<'_, T, C> {
+        DelayedReadyFuture {
+            inner: &self.inner,
+            waiting: false,
+            sleep_future: None,
+            deadline: None,
+            future_id: self.inner.counter.fetch_add(1, Ordering::Release),
         }
     }
+
+    /// Maximum number of items in the queue.
+    pub fn capacity(&self) -> usize {
+        self.inner.max_size.load(Ordering::Relaxed)
+    }
+
+    /// Changes maximum number of items in the queue. Growing wakes producers waiting
+    /// for space. Shrinking never discards items: while the queue holds more items
+    /// than the new limit, pushes wait whatever the overflow policy is.
+    pub fn set_capacity(&self, capacity: usize) {
+        let this = self.inner.as_ref();
+
+        // Меняем под блокировкой, которую держат отправители при проверке размера,
+        // чтобы уведомление не проскочило между их проверкой и засыпанием
+        let lock = this.queue.lock();
+        let previous = this.max_size.swap(capacity, Ordering::Relaxed);
+        drop(lock);
+
+        // Места прибавилось сразу для нескольких отправителей
+        if capacity > previous {
+            this.push_waiters.notify_all();
+        }
+    }
+
+    /// Number of items in the queue.
+    pub fn len(&self) -> usize {
+        self.inner.queue.lock().len()
+    }
+
+    /// Is the queue empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Wall-clock deadline of the first item in pop order.
+    /// Original time is returned for items pushed with `push_at_system_time`.
+    pub fn next_deadline(&self) -> Option<SystemTime> {
+        self.inner.queue.lock().front().map(|queue_item| {
+            queue_item.wall_deadline(self.inner.clock.now(), self.inner.clock.system_now())
+        })
+    }
+
+    /// Number of items passed to the dead letter callback because of `max_lateness`.
+    /// Overdue items are counted only when a pop future reaches them.
+    pub fn diverted_count(&self) -> u64 {
+        self.inner.diverted.load(Ordering::Relaxed)
+    }
+
+    /// Number of items evicted or dropped because of the overflow policy.
+    pub fn dropped_count(&self) -> u64 {
+        self.inner.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Number of items discarded because of expired TTL.
+    pub fn expired_count(&self) -> u64 {
+        self.inner.expired.load(Ordering::Relaxed)
+    }
+
+    // Удаляем итем по ключу, если он еще есть в очереди
+    pub(super) fn remove_by_key(&self, key: u64) -> bool {
+        // Для удобства
+        let this = self.inner.as_ref();
+
+        let mut lock = this.queue.lock();
+
+        // Ищем итем
+        let Some(position) = lock.position(this.clock.now(), |queue_item| queue_item.key == key)
+        else {
+            return false;
+        };
+
+        let removed = lock.remove(position);
+
+        drop(lock);
+
+        this.store_remove(key);
+
+        // Футура, зарезервировавшая итем, не должна проспать до его срока
+        if let Some(reservation) = removed.reserved {
+            this.reservations.kick(reservation);
+        }
+
+        // Место освободилось, а зарезервированный кем-то итем мог пропасть
+        this.notify_space();
+        this.notify_item();
+
+        true
+    }
+
+    // Возвращаем итем по ключу в конец очереди с новой задержкой
+    pub(super) fn requeue_by_key(&self, key: u64, delay: Duration) -> bool {
+        // Для удобства
+        let this = self.inner.as_ref();
+
+        let mut lock = this.queue.lock();
+
+        // Ищем итем
+        let Some(position) = lock.position(this.clock.now(), |queue_item| queue_item.key == key)
+        else {
+            return false;
+        };
+
+        let mut queue_item = lock.remove(position);
+
+        // Старое резервирование больше не действует
+        let reserved = queue_item.reserved.take();
+        queue_item.enqueued_at = this.clock.now();
+        queue_item.pop_time = queue_item.enqueued_at + delay;
+
+        // Новый срок сохраняем без блокировки, пока итема нет в очереди, а место держим
+        if this.store.is_some() {
+            lock.hold(1);
+            drop(lock);
+
+            this.store_insert_logged(&queue_item);
+
+            lock = this.queue.lock();
+            lock.release(1);
+        }
+
+        let pop_time = queue_item.pop_time;
+        let by_deadline = lock.by_deadline();
+        lock.push(queue_item);
+
+        drop(lock);
+
+        // Футура, зарезервировавшая итем, не должна проспать до его старого срока
+        if let Some(reservation) = reserved {
+            this.reservations.kick(reservation);
+        }
+
+        // Зарезервированный кем-то итем мог пропасть
+        this.notify_pushed(pop_time, by_deadline);
+
+        true
+    }
 }
 
-impl<T> Clone for DelayedQueue<T> {
+impl<T, C> Clone for DelayedQueue<T, C> {
     fn clone(&self) -> Self {
         DelayedQueue {
             inner: self.inner.clone(),
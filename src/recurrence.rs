@@ -0,0 +1,183 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Наносекунд в секунде
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Behavior of periodic items popped later than scheduled.
+/// Mirrors `tokio::time::MissedTickBehavior`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissedTickBehavior {
+    /// Next tick is scheduled right after the missed one,
+    /// so missed ticks are yielded one by one as fast as possible.
+    #[default]
+    Burst,
+
+    /// Next tick is scheduled one period after the actual pop.
+    Delay,
+
+    /// Missed ticks are skipped, next tick is aligned to the original schedule.
+    Skip,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Handle for stopping recurrence of a periodic item.
+#[derive(Debug, Clone)]
+pub struct RecurrenceHandle {
+    stopped: Arc<AtomicBool>,
+}
+
+impl RecurrenceHandle {
+    /// Stops recurrence. Already queued occurrence is discarded instead of being popped.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Release);
+    }
+
+    /// Is recurrence stopped.
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Acquire)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Как именно вычисляется следующее повторение
+enum RecurrenceKind {
+    /// Фиксированный период
+    Periodic {
+        /// Период повторения
+        period: Duration,
+
+        /// Что делаем, если итем извлекли позже нужного
+        behavior: MissedTickBehavior,
+    },
+
+    /// Календарное расписание в UTC
+    #[cfg(feature = "cron")]
+    Cron(Box<cron::Schedule>),
+}
+
+/// Параметры повторения итема
+pub(super) struct Recurrence<T> {
+    /// Как вычисляем следующее повторение
+    kind: RecurrenceKind,
+
+    /// Функция клонирования итема, чтобы не требовать `T: Clone` везде
+    clone: fn(&T) -> T,
+
+    /// Флаг остановки повторений, шарится с хендлом
+    stopped: Arc<AtomicBool>,
+}
+
+impl<T> Recurrence<T> {
+    /// Создаем новые параметры повторения вместе с хендлом остановки
+    fn new(kind: RecurrenceKind) -> (Recurrence<T>, RecurrenceHandle)
+    where
+        T: Clone,
+    {
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let recurrence = Recurrence {
+            kind,
+            clone: T::clone,
+            stopped: stopped.clone(),
+        };
+
+        (recurrence, RecurrenceHandle { stopped })
+    }
+
+    /// Повторение с фиксированным периодом
+    pub(super) fn periodic(
+        period: Duration,
+        behavior: MissedTickBehavior,
+    ) -> (Recurrence<T>, RecurrenceHandle)
+    where
+        T: Clone,
+    {
+        assert!(period > Duration::ZERO, "`period` must be non-zero.");
+
+        Recurrence::new(RecurrenceKind::Periodic { period, behavior })
+    }
+
+    /// Повторение по cron расписанию
+    #[cfg(feature = "cron")]
+    pub(super) fn cron(schedule: cron::Schedule) -> (Recurrence<T>, RecurrenceHandle)
+    where
+        T: Clone,
+    {
+        Recurrence::new(RecurrenceKind::Cron(Box::new(schedule)))
+    }
+
+    /// Остановлены ли повторения
+    pub(super) fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Acquire)
+    }
+
+    /// Копия итема для следующего повторения
+    pub(super) fn clone_item(&self, item: &T) -> T {
+        (self.clone)(item)
+    }
+
+    /// Вычисляем время следующего повторения,
+    /// `None` если повторений по расписанию больше не будет
+    pub(super) fn next_pop_time(&self, scheduled: Instant, now: Instant) -> Option<Instant> {
+        match &self.kind {
+            RecurrenceKind::Periodic { period, behavior } => {
+                let next = match behavior {
+                    MissedTickBehavior::Burst => scheduled + *period,
+                    MissedTickBehavior::Delay => now + *period,
+                    MissedTickBehavior::Skip => {
+                        // Сколько целых периодов мы пропустили
+                        let missed =
+                            now.saturating_duration_since(scheduled).as_nanos() / period.as_nanos();
+
+                        // Следующий тик выравниваем по исходному расписанию.
+                        // Сдвиг считаем в наносекундах: пропущенных периодов бывает больше u32::MAX.
+                        let offset = (missed + 1) * period.as_nanos();
+
+                        scheduled
+                            + Duration::new(
+                                (offset / NANOS_PER_SEC) as u64,
+                                (offset % NANOS_PER_SEC) as u32,
+                            )
+                    }
+                };
+
+                Some(next)
+            }
+            #[cfg(feature = "cron")]
+            RecurrenceKind::Cron(schedule) => cron_next_pop_time(schedule, now),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Ближайшее время по cron расписанию после указанного момента.
+/// Расписание считаем в UTC, а затем переводим в монотонное время.
+#[cfg(feature = "cron")]
+pub(super) fn cron_next_pop_time(schedule: &cron::Schedule, now: Instant) -> Option<Instant> {
+    use std::time::SystemTime;
+
+    // Текущее время по часам
+    let wall_now = SystemTime::now();
+
+    // Следующий момент по расписанию
+    let next: SystemTime = schedule
+        .after(&chrono::DateTime::<chrono::Utc>::from(wall_now))
+        .next()?
+        .into();
+
+    // Переводим в монотонное время
+    Some(now + next.duration_since(wall_now).unwrap_or_default())
+}
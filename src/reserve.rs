@@ -1,28 +1,208 @@
-use async_condvar_fair::Condvar;
-use std::sync::{
-    atomic::{AtomicU64, Ordering},
-    Weak,
+use crate::waiters::WaitList;
+use parking_lot::{Mutex, MutexGuard};
+use std::{
+    task::{Context, Waker},
+    time::Instant,
 };
 
 ////////////////////////////////////////////////////////////////////////////////
 
+/// Резервирование итема футурой: слот в таблице и его поколение.
+/// Итем хранит копию, поэтому добавление итема ничего не аллоцирует.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Reservation {
+    /// Индекс слота в таблице
+    index: u32,
+
+    /// Поколение слота на момент резервирования
+    generation: u32,
+}
+
+/// Слот таблицы резервирований
+#[derive(Default)]
+struct Slot {
+    /// Текущее поколение слота
+    generation: u32,
+
+    /// До какого срока спит футура-владелец на своем итеме и чем ее будить
+    sleeper: Option<(Instant, Waker)>,
+
+    /// Итем владельца убрали или появился итем раньше - ему надо перепроверить очередь
+    kicked: bool,
+}
+
+/// Слоты резервирований.
+/// Снятие резервирования увеличивает поколение слота, после чего все копии
+/// в итемах становятся недействительными без поиска самих итемов.
+#[derive(Default)]
+pub(super) struct Slots {
+    /// Слоты по индексам резервирований
+    slots: Vec<Slot>,
+
+    /// Свободные слоты для переиспользования
+    free: Vec<u32>,
+}
+
+impl Slots {
+    /// Действует ли еще резервирование итема
+    pub(super) fn is_live(&self, reservation: Reservation) -> bool {
+        self.slots[reservation.index as usize].generation == reservation.generation
+    }
+
+    /// Помечаем владельца для перепроверки, возвращая, чем его разбудить
+    fn kick(&mut self, index: usize) -> Option<Waker> {
+        let slot = &mut self.slots[index];
+        slot.kicked = true;
+        slot.sleeper.take().map(|(_, waker)| waker)
+    }
+}
+
+/// Таблица резервирований очереди.
+/// Емкость растет до числа одновременно ждущих футур и дальше переиспользуется.
+#[derive(Default)]
+pub(super) struct Reservations {
+    slots: Mutex<Slots>,
+}
+
+impl Reservations {
+    /// Блокировка для проверки резервирований, берется под блокировкой очереди
+    pub(super) fn lock(&self) -> MutexGuard<'_, Slots> {
+        self.slots.lock()
+    }
+
+    /// Новое резервирование в свободном слоте, владелец спит до `deadline`
+    fn acquire(&self, deadline: Instant, cx: &Context<'_>) -> Reservation {
+        let mut slots = self.slots.lock();
+
+        let index = match slots.free.pop() {
+            Some(index) => index,
+            None => {
+                slots.slots.push(Slot::default());
+                u32::try_from(slots.slots.len() - 1).expect("Too many reservations")
+            }
+        };
+
+        let slot = &mut slots.slots[index as usize];
+        slot.sleeper = Some((deadline, cx.waker().clone()));
+
+        Reservation {
+            index,
+            generation: slot.generation,
+        }
+    }
+
+    /// Снимаем резервирование, слот уходит в свободные
+    fn release(&self, reservation: Reservation) {
+        let mut slots = self.slots.lock();
+
+        let slot = &mut slots.slots[reservation.index as usize];
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.sleeper = None;
+        slot.kicked = false;
+
+        slots.free.push(reservation.index);
+    }
+
+    /// Проверяем, не надо ли владельцу перепроверить очередь, забирая пометку.
+    /// Иначе обновляем, чем его будить.
+    pub(super) fn poll_kicked(&self, reservation: Reservation, cx: &Context<'_>) -> bool {
+        let mut slots = self.slots.lock();
+
+        let slot = &mut slots.slots[reservation.index as usize];
+        if std::mem::take(&mut slot.kicked) {
+            return true;
+        }
+
+        if let Some((_, waker)) = slot.sleeper.as_mut() {
+            if !waker.will_wake(cx.waker()) {
+                *waker = cx.waker().clone();
+            }
+        }
+
+        false
+    }
+
+    /// Итем убрали из очереди - будим футуру, если она еще ждет его
+    pub(super) fn kick(&self, reservation: Reservation) {
+        let mut slots = self.slots.lock();
+
+        if !slots.is_live(reservation) {
+            return;
+        }
+        let waker = slots.kick(reservation.index as usize);
+
+        // Будим уже без блокировки
+        drop(slots);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Появился итем со сроком `deadline` - будим футуру, которая спит дольше всех,
+    /// если она спит дольше него, чтобы она забрала новый итем вместо своего
+    pub(super) fn kick_later(&self, deadline: Instant) {
+        let mut slots = self.slots.lock();
+
+        let latest = slots
+            .slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| match slot.sleeper.as_ref() {
+                Some((sleep_until, _)) if *sleep_until > deadline => Some((index, *sleep_until)),
+                _ => None,
+            })
+            .max_by_key(|(_, sleep_until)| *sleep_until);
+
+        let Some((index, _)) = latest else {
+            return;
+        };
+        let waker = slots.kick(index);
+
+        // Будим уже без блокировки
+        drop(slots);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 pub(super) struct ReserveWaker<'a> {
-    /// Пробуждалка для резервирования
-    pub(super) condvar: &'a Condvar,
-    
-    /// Используется Arc, так как очередь у нас под блокировкой
-    pub(super) item_reserved_future: Weak<AtomicU64>,
+    /// Футуры, ждущие свободного итема
+    waiters: &'a WaitList,
+
+    /// Таблица, в которой держим резервирование
+    reservations: &'a Reservations,
+
+    /// Резервирование итема этой футурой
+    pub(super) reservation: Reservation,
+}
+
+impl<'a> ReserveWaker<'a> {
+    /// Заводим новое резервирование итема со сроком `deadline`, снимется оно при уничтожении
+    pub(super) fn new(
+        waiters: &'a WaitList,
+        reservations: &'a Reservations,
+        deadline: Instant,
+        cx: &Context<'_>,
+    ) -> ReserveWaker<'a> {
+        ReserveWaker {
+            waiters,
+            reservations,
+            reservation: reservations.acquire(deadline, cx),
+        }
+    }
 }
 
 impl<'a> Drop for ReserveWaker<'a> {
     fn drop(&mut self) {
-        // Никто больше не резервирует итем
-        if let Some(still_reserved) = self.item_reserved_future.upgrade() {
-            // Снимаем статус резервирования
-            still_reserved.store(0, Ordering::Release);
-        }
+        // Снимаем статус резервирования, итем при этом искать не нужно
+        self.reservations.release(self.reservation);
 
         // Всегда уведомляем кого-то, кто ждет результат, а не только при отмене.
-        self.condvar.notify_one();
+        self.waiters.notify_one();
     }
 }
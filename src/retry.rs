@@ -0,0 +1,273 @@
+use crate::{
+    future::DelayedPopFuture,
+    queue::DelayedQueue,
+    rng::{duration_between, RandomSource, SplitMix64},
+};
+use parking_lot::Mutex;
+use std::{sync::Arc, time::Duration};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Backoff policy for computing delay before the next attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backoff {
+    /// `initial * factor^(attempt - 1)`, capped by `max`.
+    Exponential {
+        /// Delay before the second attempt.
+        initial: Duration,
+        /// Multiplier applied after every attempt.
+        factor: u32,
+        /// Delay cap.
+        max: Duration,
+    },
+
+    /// `initial + step * (attempt - 1)`, capped by `max`.
+    Linear {
+        /// Delay before the second attempt.
+        initial: Duration,
+        /// Increment applied after every attempt.
+        step: Duration,
+        /// Delay cap.
+        max: Duration,
+    },
+
+    /// "Decorrelated jitter": random delay between `base` and three times
+    /// the previous delay, capped by `max`.
+    DecorrelatedJitter {
+        /// Minimal delay.
+        base: Duration,
+        /// Delay cap.
+        max: Duration,
+    },
+}
+
+impl Backoff {
+    /// Вычисляем задержку перед следующей попыткой после попытки номер `attempt`
+    fn delay(&self, attempt: u32, previous: Duration, rng: &mut dyn RandomSource) -> Duration {
+        // Сколько раз уже откладывали
+        let retries = attempt.saturating_sub(1);
+
+        match *self {
+            Backoff::Exponential {
+                initial,
+                factor,
+                max,
+            } => factor
+                .checked_pow(retries)
+                .and_then(|multiplier| initial.checked_mul(multiplier))
+                .unwrap_or(max)
+                .min(max),
+            Backoff::Linear { initial, step, max } => step
+                .checked_mul(retries)
+                .and_then(|increment| initial.checked_add(increment))
+                .unwrap_or(max)
+                .min(max),
+            Backoff::DecorrelatedJitter { base, max } => {
+                // Для первой попытки предыдущей задержкой считаем базовую
+                let previous = previous.max(base);
+
+                duration_between(rng, base, previous.saturating_mul(3)).min(max)
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Item popped from [`RetryQueue`] along with its attempt number.
+#[derive(Debug)]
+pub struct RetryItem<T> {
+    /// Сам итем
+    item: T,
+
+    /// Номер текущей попытки, начиная с 1
+    attempt: u32,
+
+    /// Задержка отсрочки перед текущей попыткой, ноль для первой попытки
+    delay: Duration,
+}
+
+impl<T> RetryItem<T> {
+    /// Current attempt number, starting from 1.
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Reference to the item.
+    pub fn item(&self) -> &T {
+        &self.item
+    }
+
+    /// Takes the item, no more retries are possible.
+    pub fn into_inner(self) -> T {
+        self.item
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Callback receiving items which exhausted all attempts and the number of attempts made.
+type DeadLetter<T> = Box<dyn Fn(T, u32) + Send + Sync>;
+
+/// Общие настройки повторов
+struct Shared<T> {
+    /// Политика задержек
+    backoff: Backoff,
+
+    /// Максимальное количество попыток
+    max_attempts: u32,
+
+    /// Куда отдаем итемы, у которых закончились попытки
+    dead_letter: Option<DeadLetter<T>>,
+
+    /// Генератор для случайных задержек
+    random_source: Mutex<Box<dyn RandomSource>>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Delayed queue for retrying jobs with backoff.
+///
+/// ```rust
+/// # use tokio_delayed_queue::{Backoff, RetryQueue};
+/// # use std::time::Duration;
+/// # tokio_test::block_on(async {
+///
+/// let backoff = Backoff::Exponential {
+///     initial: Duration::from_millis(10),
+///     factor: 2,
+///     max: Duration::from_secs(1),
+/// };
+/// let queue = RetryQueue::new(16, backoff, 3).with_dead_letter(|item, attempts| {
+///     println!("{item} failed after {attempts} attempts");
+/// });
+///
+/// queue.push("job", Duration::ZERO).await;
+///
+/// // Failed, retry later
+/// let job = queue.pop().await;
+/// assert_eq!(job.attempt(), 1);
+/// assert!(queue.retry(job).await);
+///
+/// let job = queue.pop().await;
+/// assert_eq!(job.attempt(), 2);
+///
+/// # });
+/// ```
+pub struct RetryQueue<T> {
+    queue: DelayedQueue<RetryItem<T>>,
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> RetryQueue<T> {
+    /// Creates new retry queue with fixed capacity, backoff policy and maximum number of attempts.
+    pub fn new(size: usize, backoff: Backoff, max_attempts: u32) -> RetryQueue<T> {
+        RetryQueue {
+            queue: DelayedQueue::new(size),
+            shared: Arc::new(Shared {
+                backoff,
+                max_attempts,
+                dead_letter: None,
+                random_source: Mutex::new(Box::new(SplitMix64::from_time())),
+            }),
+        }
+    }
+
+    /// Sets callback receiving items which exhausted all attempts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the queue was already cloned.
+    pub fn with_dead_letter<F>(mut self, dead_letter: F) -> RetryQueue<T>
+    where
+        F: Fn(T, u32) + Send + Sync + 'static,
+    {
+        Arc::get_mut(&mut self.shared)
+            .expect("Dead letter must be set before cloning the queue")
+            .dead_letter = Some(Box::new(dead_letter));
+        self
+    }
+
+    /// Sets random source used by [`Backoff::DecorrelatedJitter`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the queue was already cloned.
+    pub fn with_random_source<R>(mut self, random_source: R) -> RetryQueue<T>
+    where
+        R: RandomSource + 'static,
+    {
+        Arc::get_mut(&mut self.shared)
+            .expect("Random source must be set before cloning the queue")
+            .random_source = Mutex::new(Box::new(random_source));
+        self
+    }
+
+    /// Push new job, first attempt happens after `delay`.
+    pub async fn push(&self, item: T, delay: Duration) {
+        // Задержка первой попытки задана снаружи и не участвует в отсрочке
+        let item = RetryItem {
+            item,
+            attempt: 1,
+            delay: Duration::ZERO,
+        };
+
+        // Очередь ждет места и не взвешивает итемы, поэтому не отвергает их
+        self.queue
+            .push(item, delay)
+            .await
+            .expect("Retry queue should not reject items");
+    }
+
+    /// Atomically pop the next attempt. It supports pop cancelation by returned future drop.
+    pub fn pop(&self) -> DelayedPopFuture<'_, RetryItem<T>> {
+        self.queue.pop()
+    }
+
+    /// Schedules the next attempt with backoff delay. If attempts are exhausted
+    /// the item is passed to the dead letter callback and `false` is returned.
+    pub async fn retry(&self, item: RetryItem<T>) -> bool {
+        let RetryItem {
+            item,
+            attempt,
+            delay,
+        } = item;
+
+        // Попытки закончились
+        if attempt >= self.shared.max_attempts {
+            if let Some(dead_letter) = self.shared.dead_letter.as_ref() {
+                dead_letter(item, attempt);
+            }
+            return false;
+        }
+
+        // Задержка перед следующей попыткой
+        let delay =
+            self.shared
+                .backoff
+                .delay(attempt, delay, self.shared.random_source.lock().as_mut());
+
+        let item = RetryItem {
+            item,
+            attempt: attempt + 1,
+            delay,
+        };
+
+        // Очередь ждет места и не взвешивает итемы, поэтому не отвергает их
+        self.queue
+            .push(item, delay)
+            .await
+            .expect("Retry queue should not reject items");
+
+        true
+    }
+}
+
+impl<T> Clone for RetryQueue<T> {
+    fn clone(&self) -> Self {
+        RetryQueue {
+            queue: self.queue.clone(),
+            shared: self.shared.clone(),
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Счетчик для того, чтобы генераторы созданные одновременно различались
+static SEED_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Source of random numbers used for jitter and randomized backoff.
+/// Implement it with a fixed sequence to get deterministic delays in tests.
+pub trait RandomSource: Send {
+    /// Next random number.
+    fn next_u64(&mut self) -> u64;
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Default non-cryptographic SplitMix64 random generator.
+#[derive(Debug, Clone)]
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    /// Creates generator with fixed seed, useful for reproducible tests.
+    pub fn new(seed: u64) -> SplitMix64 {
+        SplitMix64(seed)
+    }
+
+    /// Creates generator seeded from current time.
+    pub fn from_time() -> SplitMix64 {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64;
+
+        let counter = SEED_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+        SplitMix64(nanos ^ counter.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+    }
+}
+
+impl Default for SplitMix64 {
+    fn default() -> Self {
+        SplitMix64::from_time()
+    }
+}
+
+impl RandomSource for SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Случайная длительность в диапазоне `[from, to]`
+pub(super) fn duration_between(
+    rng: &mut dyn RandomSource,
+    from: Duration,
+    to: Duration,
+) -> Duration {
+    if to <= from {
+        return from;
+    }
+
+    let span = (to - from).as_nanos().min(u64::MAX as u128) as u64;
+
+    from + Duration::from_nanos(rng.next_u64() % span.saturating_add(1))
+}
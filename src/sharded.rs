@@ -0,0 +1,228 @@
+use crate::{
+    clock::{Clock, DefaultClock},
+    error::PushError,
+    future::DelayedPopFuture,
+    queue::DelayedQueue,
+};
+use std::{
+    collections::hash_map::DefaultHasher,
+    future::Future,
+    hash::{Hash, Hasher},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, SystemTime},
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Общие данные шардированной очереди
+struct ShardedInner<T, C> {
+    /// Независимые очереди со своими блокировками
+    shards: Vec<DelayedQueue<T, C>>,
+
+    /// Счетчик для равномерного распределения добавлений
+    next_push: AtomicUsize,
+
+    /// Счетчик для равномерного распределения извлечений
+    next_pop: AtomicUsize,
+}
+
+/// Delayed queue split into independent shards to reduce lock contention
+/// between many producers.
+///
+/// Items are distributed between shards round-robin or by key hash, every shard
+/// has its own lock. Pop waits on all shards at once and returns the item
+/// which becomes due first, so poppers see a merged earliest-deadline view.
+/// Order of items is kept only within a shard.
+///
+/// ```rust
+/// # use tokio_delayed_queue::ShardedDelayedQueue;
+/// # use std::time::Duration;
+/// # tokio_test::block_on(async {
+///
+/// let queue = ShardedDelayedQueue::new(64, 4);
+///
+/// queue.push(1, Duration::from_millis(20)).await.unwrap();
+/// queue.push(2, Duration::from_millis(10)).await.unwrap();
+///
+/// assert_eq!(queue.pop().await, 2);
+/// assert_eq!(queue.pop().await, 1);
+///
+/// # });
+/// ```
+pub struct ShardedDelayedQueue<T, C = DefaultClock> {
+    inner: Arc<ShardedInner<T, C>>,
+}
+
+impl<T> ShardedDelayedQueue<T> {
+    /// Creates queue with `shards` shards and total capacity `size` split evenly between them,
+    /// the first `size % shards` shards get one extra slot.
+    /// `size` should be at least `shards`, so that every shard can hold an item.
+    pub fn new(size: usize, shards: usize) -> ShardedDelayedQueue<T> {
+        assert!(shards > 0, "Shards count should be positive");
+        assert!(size >= shards, "Size should not be less than shards count");
+
+        // Остаток раздаем первым шардам, чтобы суммарная емкость была ровно `size`
+        ShardedDelayedQueue::from_shards(
+            (0..shards)
+                .map(|index| DelayedQueue::new(size / shards + usize::from(index < size % shards))),
+        )
+    }
+}
+
+impl<T, C: Clock> ShardedDelayedQueue<T, C> {
+    /// Creates queue from separately configured shards.
+    pub fn from_shards<I>(shards: I) -> ShardedDelayedQueue<T, C>
+    where
+        I: IntoIterator<Item = DelayedQueue<T, C>>,
+    {
+        let shards = shards.into_iter().collect::<Vec<_>>();
+
+        assert!(!shards.is_empty(), "Shards count should be positive");
+
+        ShardedDelayedQueue {
+            inner: Arc::new(ShardedInner {
+                shards,
+                next_push: AtomicUsize::new(0),
+                next_pop: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Push new item to the next shard in round-robin order.
+    /// Fails like [`DelayedQueue::push`] of the shard.
+    pub async fn push(&self, item: T, delay: Duration) -> Result<(), PushError<T>> {
+        let index = self.inner.next_push.fetch_add(1, Ordering::Relaxed);
+
+        self.shard(index).push(item, delay).await
+    }
+
+    /// Push new item to the shard chosen by `key` hash,
+    /// items with equal keys keep their relative order.
+    pub async fn push_hashed<K>(
+        &self,
+        key: &K,
+        item: T,
+        delay: Duration,
+    ) -> Result<(), PushError<T>>
+    where
+        K: Hash + ?Sized,
+    {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+
+        self.shard(hasher.finish() as usize).push(item, delay).await
+    }
+
+    /// Atomically pop the item which becomes due first among all shards.
+    /// It supports pop cancelation by returned future drop.
+    ///
+    /// Unlike [`DelayedQueue::pop`] the future allocates: every shard it has to poll
+    /// gets a boxed pop future, so a pop waiting on all shards makes `shards + 1` allocations.
+    /// A pop served by the first polled shard makes two.
+    pub fn pop(&self) -> ShardedPopFuture<'_, T, C> {
+        // Начинаем опрос каждый раз с другого шарда, чтобы готовые итемы разбирались равномерно
+        let start = self.inner.next_pop.fetch_add(1, Ordering::Relaxed) % self.shards_count();
+
+        ShardedPopFuture {
+            shards: &self.inner.shards,
+            pops: Vec::new(),
+            start,
+        }
+    }
+
+    /// Number of shards.
+    pub fn shards_count(&self) -> usize {
+        self.inner.shards.len()
+    }
+
+    /// Total capacity of all shards.
+    pub fn capacity(&self) -> usize {
+        self.inner.shards.iter().map(DelayedQueue::capacity).sum()
+    }
+
+    /// Number of items in all shards.
+    pub fn len(&self) -> usize {
+        self.inner.shards.iter().map(DelayedQueue::len).sum()
+    }
+
+    /// Returns `true` if all shards are empty.
+    pub fn is_empty(&self) -> bool {
+        self.inner.shards.iter().all(DelayedQueue::is_empty)
+    }
+
+    /// Earliest wall-clock deadline among the first items of shards.
+    pub fn next_deadline(&self) -> Option<SystemTime> {
+        self.inner
+            .shards
+            .iter()
+            .filter_map(DelayedQueue::next_deadline)
+            .min()
+    }
+
+    // Шард по произвольному индексу
+    fn shard(&self, index: usize) -> &DelayedQueue<T, C> {
+        &self.inner.shards[index % self.inner.shards.len()]
+    }
+}
+
+impl<T, C> Clone for ShardedDelayedQueue<T, C> {
+    fn clone(&self) -> Self {
+        ShardedDelayedQueue {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Sharded delayed queue pop future.
+// Ждем сразу на всех шардах, каждый шард резервирует свой итем.
+// Как только один из них готов, остальные футуры уничтожаются вместе с нами
+// и снимают свои резервирования.
+// Футуры шардов создаются по мере опроса: если в первом же шарде есть готовый итем,
+// до остальных шардов дело не доходит.
+pub struct ShardedPopFuture<'a, T, C: Clock = DefaultClock> {
+    /// Шарды очереди
+    shards: &'a [DelayedQueue<T, C>],
+
+    /// Футуры извлечения по шардам, начиная с первого опрашиваемого.
+    /// Футура шарда хранит сон внутри себя и не Unpin, поэтому каждая лежит в своем Box.
+    pops: Vec<Pin<Box<DelayedPopFuture<'a, T, C>>>>,
+
+    /// С какого шарда начинаем опрос
+    start: usize,
+}
+
+impl<'a, T, C: Clock> Future for ShardedPopFuture<'a, T, C> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        let shards = this.shards;
+        for offset in 0..shards.len() {
+            if offset == this.pops.len() {
+                // Место под футуры всех шардов выделяем сразу, без перевыделений
+                if this.pops.is_empty() {
+                    this.pops.reserve_exact(shards.len());
+                }
+
+                let index = (this.start + offset) % shards.len();
+                this.pops.push(Box::pin(shards[index].pop()));
+            }
+
+            // Итем извлекается только в готовой футуре, поэтому после первой же
+            // готовой остальные опрашивать нельзя, иначе потеряем итемы
+            if let Poll::Ready(item) = this.pops[offset].as_mut().poll(cx) {
+                return Poll::Ready(item);
+            }
+        }
+
+        Poll::Pending
+    }
+}
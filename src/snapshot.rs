@@ -0,0 +1,109 @@
+use crate::{
+    builder::DelayedQueueBuilder,
+    clock::Clock,
+    item::{to_instant, to_system_time, DelayItem},
+    queue::DelayedQueue,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Item of a [`QueueSnapshot`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotItem<T> {
+    /// The item itself.
+    pub item: T,
+
+    /// Wall-clock time when the item becomes poppable,
+    /// original time for items pushed with `push_at_system_time`.
+    pub deadline: SystemTime,
+
+    /// Remaining delay at the moment of the snapshot.
+    pub remaining: Duration,
+
+    /// Wall-clock time after which the item is discarded, if it has TTL.
+    pub expires_at: Option<SystemTime>,
+}
+
+/// Serializable copy of queue contents in queue order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueSnapshot<T> {
+    /// Items in queue order.
+    pub items: Vec<SnapshotItem<T>>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+impl<T, C: Clock> DelayedQueue<T, C> {
+    /// Copies queue contents. Items reserved by in-flight pop futures are included
+    /// as regular items since they are still in the queue, copies of items popped
+    /// with `pop_ack` are included with their visibility timeout as the deadline.
+    /// Periodic items are included as their next occurrence only.
+    pub fn snapshot(&self) -> QueueSnapshot<T>
+    where
+        T: Clone,
+    {
+        let now = self.inner.clock.now();
+        let system_now = self.inner.clock.system_now();
+
+        let items = self
+            .inner
+            .queue
+            .lock()
+            .iter()
+            .filter(|queue_item| !queue_item.is_stopped())
+            .map(|queue_item| SnapshotItem {
+                item: queue_item.item.clone(),
+                deadline: queue_item.wall_deadline(now, system_now),
+                remaining: queue_item.pop_time.saturating_duration_since(now),
+                expires_at: queue_item
+                    .expires_at
+                    .map(|expires_at| to_system_time(expires_at, now, system_now)),
+            })
+            .collect();
+
+        QueueSnapshot { items }
+    }
+}
+
+impl<T> DelayedQueue<T> {
+    /// Creates queue with fixed capacity from a snapshot. Items keep their
+    /// absolute deadlines, items already overdue become poppable immediately.
+    /// Restored items are not reserved by anyone.
+    pub fn restore(snapshot: QueueSnapshot<T>, size: usize) -> DelayedQueue<T> {
+        DelayedQueue::builder(size).build_from_snapshot(snapshot)
+    }
+}
+
+impl<T, C: Clock> DelayedQueueBuilder<T, C> {
+    /// Creates the queue filled with items of the snapshot, see [`DelayedQueue::restore`].
+    pub fn build_from_snapshot(self, snapshot: QueueSnapshot<T>) -> DelayedQueue<T, C> {
+        let mut queue = self.build();
+
+        let inner = Arc::get_mut(&mut queue.inner).expect("Queue should not be shared yet");
+
+        let now = inner.clock.now();
+        let system_now = inner.clock.system_now();
+
+        for snapshot_item in snapshot.items {
+            let mut queue_item = DelayItem::new(
+                snapshot_item.item,
+                to_instant(snapshot_item.deadline, now, system_now),
+                now,
+            );
+            queue_item.key = inner.next_key();
+            queue_item.expires_at = snapshot_item
+                .expires_at
+                .map(|expires_at| to_instant(expires_at, now, system_now));
+            queue_item.system_time = Some(snapshot_item.deadline);
+
+            inner.queue.get_mut().push(queue_item);
+        }
+
+        queue
+    }
+}
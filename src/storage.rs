@@ -0,0 +1,291 @@
+use crate::{item::DelayItem, reserve::Reservation, wheel::TimingWheel};
+use std::{
+    cmp::Reverse,
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Storage backend of the queue, selected at construction with
+/// [`DelayedQueueBuilder::backend`](crate::DelayedQueueBuilder::backend).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// Items are kept in push order, every waiting pop future takes
+    /// the first item not reserved by others. Good for small queues.
+    #[default]
+    Fifo,
+
+    /// Hierarchical timing wheel with ticks of `resolution`: push and pop cost O(1)
+    /// regardless of the number of pending items. Items are popped in deadline order,
+    /// items within one tick in push order.
+    TimingWheel {
+        /// Tick length, deadlines are ordered with this granularity.
+        resolution: Duration,
+    },
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Позиция итема в хранилище, действительна до изменения хранилища
+#[derive(Clone, Copy)]
+pub(super) struct Position(usize, usize);
+
+/// Функция подсчета веса итема
+pub(super) type Weigher<T> = Box<dyn Fn(&T) -> usize + Send + Sync>;
+
+/// Итемы в выбранной структуре
+enum Items<T> {
+    /// Итемы в порядке добавления
+    Fifo(VecDeque<DelayItem<T>>),
+
+    /// Итемы в колесе таймеров по времени срабатывания
+    Wheel(TimingWheel<T>),
+}
+
+/// Хранилище итемов очереди с учетом их суммарного веса
+pub(super) struct Storage<T> {
+    items: Items<T>,
+
+    /// Как считаем вес итема, без него вес всех итемов нулевой
+    weigher: Option<Weigher<T>>,
+
+    /// Суммарный вес итемов
+    weight: usize,
+
+    /// Места, занятые итемами, которые вернутся в очередь после записи в хранилище
+    held: usize,
+}
+
+impl<T> Storage<T> {
+    pub(super) fn new(
+        backend: Backend,
+        capacity: usize,
+        now: Instant,
+        weigher: Option<Weigher<T>>,
+    ) -> Storage<T> {
+        let items = match backend {
+            // Аллоцируем сразу же нужный размер один раз
+            Backend::Fifo => Items::Fifo(VecDeque::with_capacity(capacity)),
+            Backend::TimingWheel { resolution } => Items::Wheel(TimingWheel::new(resolution, now)),
+        };
+
+        Storage {
+            items,
+            weigher,
+            weight: 0,
+            held: 0,
+        }
+    }
+
+    pub(super) fn len(&self) -> usize {
+        match &self.items {
+            Items::Fifo(items) => items.len(),
+            Items::Wheel(wheel) => wheel.len(),
+        }
+    }
+
+    /// Сколько мест занято, вместе с удержанными
+    pub(super) fn occupied(&self) -> usize {
+        self.len() + self.held
+    }
+
+    /// Удерживаем места под итемы, которые вернутся в очередь позже
+    pub(super) fn hold(&mut self, count: usize) {
+        self.held += count;
+    }
+
+    /// Отпускаем удержанные места перед возвратом итемов
+    pub(super) fn release(&mut self, count: usize) {
+        self.held -= count;
+    }
+
+    /// Отдаются ли итемы в порядке сроков, а не добавления
+    pub(super) fn by_deadline(&self) -> bool {
+        matches!(self.items, Items::Wheel(_))
+    }
+
+    /// Суммарный вес итемов
+    pub(super) fn weight(&self) -> usize {
+        self.weight
+    }
+
+    /// Вес итема
+    pub(super) fn weigh(&self, item: &T) -> usize {
+        self.weigher.as_ref().map_or(0, |weigher| weigher(item))
+    }
+
+    /// Добавляем итем, его вес запоминаем для вычитания при удалении
+    pub(super) fn push(&mut self, mut queue_item: DelayItem<T>) {
+        queue_item.weight = self.weigh(&queue_item.item);
+        self.push_weighed(queue_item);
+    }
+
+    /// Добавляем итем с уже посчитанным весом
+    pub(super) fn push_weighed(&mut self, queue_item: DelayItem<T>) {
+        self.weight += queue_item.weight;
+
+        match &mut self.items {
+            Items::Fifo(items) => items.push_back(queue_item),
+            Items::Wheel(wheel) => wheel.push(queue_item),
+        }
+    }
+
+    /// Первый в порядке извлечения итем, подходящий под условие.
+    /// Колесо сначала продвигаем к текущему моменту.
+    pub(super) fn position<P>(&mut self, now: Instant, predicate: P) -> Option<Position>
+    where
+        P: FnMut(&DelayItem<T>) -> bool,
+    {
+        match &mut self.items {
+            Items::Fifo(items) => items
+                .iter()
+                .position(predicate)
+                .map(|index| Position(0, index)),
+            Items::Wheel(wheel) => {
+                wheel.advance(now);
+                wheel
+                    .position(predicate)
+                    .map(|(bucket, index)| Position(bucket, index))
+            }
+        }
+    }
+
+    pub(super) fn get(&self, Position(bucket, index): Position) -> &DelayItem<T> {
+        match &self.items {
+            Items::Fifo(items) => &items[index],
+            Items::Wheel(wheel) => wheel.get((bucket, index)),
+        }
+    }
+
+    pub(super) fn get_mut(&mut self, Position(bucket, index): Position) -> &mut DelayItem<T> {
+        match &mut self.items {
+            Items::Fifo(items) => &mut items[index],
+            Items::Wheel(wheel) => wheel.get_mut((bucket, index)),
+        }
+    }
+
+    pub(super) fn remove(&mut self, Position(bucket, index): Position) -> DelayItem<T> {
+        let queue_item = match &mut self.items {
+            Items::Fifo(items) => items.remove(index).expect("Item should exist"),
+            Items::Wheel(wheel) => wheel.remove((bucket, index)),
+        };
+
+        self.weight -= queue_item.weight;
+
+        queue_item
+    }
+
+    /// Первый в порядке извлечения итем
+    pub(super) fn front(&self) -> Option<&DelayItem<T>> {
+        match &self.items {
+            Items::Fifo(items) => items.front(),
+            Items::Wheel(wheel) => wheel.position(|_| true).map(|position| wheel.get(position)),
+        }
+    }
+
+    /// Снимаем резервирование с итема, если оно на нем еще стоит.
+    /// Колесо не продвигаем, чтобы не сдвинуть уже найденные позиции.
+    pub(super) fn unreserve(&mut self, reservation: Reservation) {
+        let predicate = |queue_item: &DelayItem<T>| queue_item.reserved == Some(reservation);
+
+        let position = match &self.items {
+            Items::Fifo(items) => items
+                .iter()
+                .position(predicate)
+                .map(|index| Position(0, index)),
+            Items::Wheel(wheel) => wheel
+                .position(predicate)
+                .map(|(bucket, index)| Position(bucket, index)),
+        };
+
+        if let Some(position) = position {
+            self.get_mut(position).reserved = None;
+        }
+    }
+
+    /// Удаляем сразу несколько итемов
+    pub(super) fn remove_all(&mut self, mut positions: Vec<Position>) -> Vec<DelayItem<T>> {
+        // Удаляем с конца, чтобы позиции остальных не сдвигались
+        positions.sort_unstable_by_key(|&Position(bucket, index)| Reverse((bucket, index)));
+
+        positions
+            .into_iter()
+            .map(|position| self.remove(position))
+            .collect()
+    }
+
+    /// Самые старые итемы, вытеснение которых освобождает `len` мест и `weight` веса
+    pub(super) fn oldest_victims(&self, len: usize, weight: usize) -> Option<Vec<Position>> {
+        match &self.items {
+            Items::Fifo(_) => take_victims(self.positions(), len, weight),
+            Items::Wheel(_) => {
+                let mut items = self.positions().collect::<Vec<_>>();
+                items.sort_unstable_by_key(|(_, queue_item)| queue_item.key);
+
+                take_victims(items.into_iter(), len, weight)
+            }
+        }
+    }
+
+    /// Итемы со сроком позже `pop_time`, начиная с самого позднего,
+    /// вытеснение которых освобождает `len` мест и `weight` веса
+    pub(super) fn latest_victims(
+        &self,
+        pop_time: Instant,
+        len: usize,
+        weight: usize,
+    ) -> Option<Vec<Position>> {
+        let mut items = self
+            .positions()
+            .filter(|(_, queue_item)| queue_item.pop_time > pop_time)
+            .collect::<Vec<_>>();
+        items.sort_by_key(|(_, queue_item)| Reverse(queue_item.pop_time));
+
+        take_victims(items.into_iter(), len, weight)
+    }
+
+    /// Все итемы в порядке извлечения
+    pub(super) fn iter(&self) -> impl Iterator<Item = &DelayItem<T>> {
+        self.positions().map(|(_, queue_item)| queue_item)
+    }
+
+    /// Все итемы с их позициями в порядке извлечения
+    fn positions(&self) -> Box<dyn Iterator<Item = (Position, &DelayItem<T>)> + '_> {
+        match &self.items {
+            Items::Fifo(items) => Box::new(
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(index, queue_item)| (Position(0, index), queue_item)),
+            ),
+            Items::Wheel(wheel) => Box::new(
+                wheel
+                    .iter()
+                    .map(|((bucket, index), queue_item)| (Position(bucket, index), queue_item)),
+            ),
+        }
+    }
+}
+
+/// Набираем итемы по порядку, пока они не освободят `len` мест и `weight` веса.
+/// Если всех подходящих не хватает, не вытесняем никого.
+fn take_victims<'a, T: 'a>(
+    items: impl Iterator<Item = (Position, &'a DelayItem<T>)>,
+    len: usize,
+    weight: usize,
+) -> Option<Vec<Position>> {
+    let mut victims = Vec::new();
+    let mut freed_weight = 0;
+
+    for (position, queue_item) in items {
+        if victims.len() >= len && freed_weight >= weight {
+            break;
+        }
+
+        victims.push(position);
+        freed_weight += queue_item.weight;
+    }
+
+    (victims.len() >= len && freed_weight >= weight).then_some(victims)
+}
@@ -0,0 +1,115 @@
+use parking_lot::Mutex;
+use std::{collections::VecDeque, io, sync::Arc, time::SystemTime};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Item loaded from a [`DelayStore`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredItem<T> {
+    /// Unique key of the item in the queue.
+    pub key: u64,
+
+    /// The item itself.
+    pub item: T,
+
+    /// Wall-clock time when the item becomes poppable.
+    pub deadline: SystemTime,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Persistent storage of delayed items.
+///
+/// The queue mirrors every change of its contents into the store and replays
+/// the stored items on startup with [`DelayedQueueBuilder::build_with_store`].
+///
+/// Methods are synchronous and called by pushing and popping tasks outside of the queue lock:
+/// a slow store delays only the tasks which change stored items, but blocks their
+/// executor threads. An item is saved before it becomes visible in the queue,
+/// so its removal never overtakes its insertion.
+///
+/// [`DelayedQueueBuilder::build_with_store`]: crate::DelayedQueueBuilder::build_with_store
+pub trait DelayStore<T>: Send + Sync {
+    /// Inserts the item or updates the deadline of an existing one with the same key.
+    fn insert(&self, key: u64, item: &T, deadline: SystemTime) -> io::Result<()>;
+
+    /// Removes the item.
+    fn remove(&self, key: u64) -> io::Result<()>;
+
+    /// Loads all stored items ordered by insertion.
+    fn load_all(&self) -> io::Result<Vec<StoredItem<T>>>;
+
+    /// Earliest deadline among stored items.
+    fn next_due(&self) -> Option<SystemTime>;
+}
+
+// Хранилище можно держать в Arc, чтобы переиспользовать его после пересоздания очереди
+impl<T, S> DelayStore<T> for Arc<S>
+where
+    S: DelayStore<T> + ?Sized,
+{
+    fn insert(&self, key: u64, item: &T, deadline: SystemTime) -> io::Result<()> {
+        self.as_ref().insert(key, item, deadline)
+    }
+
+    fn remove(&self, key: u64) -> io::Result<()> {
+        self.as_ref().remove(key)
+    }
+
+    fn load_all(&self) -> io::Result<Vec<StoredItem<T>>> {
+        self.as_ref().load_all()
+    }
+
+    fn next_due(&self) -> Option<SystemTime> {
+        self.as_ref().next_due()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// In-memory [`DelayStore`], keeps items alive while the process is running.
+/// Useful for tests and for handing items over between queue instances.
+#[derive(Debug, Default)]
+pub struct MemoryStore<T> {
+    items: Mutex<VecDeque<StoredItem<T>>>,
+}
+
+impl<T> MemoryStore<T> {
+    /// Creates empty store.
+    pub fn new() -> MemoryStore<T> {
+        MemoryStore {
+            items: Mutex::new(VecDeque::new()),
+        }
+    }
+}
+
+impl<T: Clone + Send> DelayStore<T> for MemoryStore<T> {
+    fn insert(&self, key: u64, item: &T, deadline: SystemTime) -> io::Result<()> {
+        let mut items = self.items.lock();
+
+        // Такой итем уже есть - просто обновляем время
+        match items.iter_mut().find(|stored| stored.key == key) {
+            Some(stored) => stored.deadline = deadline,
+            None => items.push_back(StoredItem {
+                key,
+                item: item.clone(),
+                deadline,
+            }),
+        }
+
+        Ok(())
+    }
+
+    fn remove(&self, key: u64) -> io::Result<()> {
+        self.items.lock().retain(|stored| stored.key != key);
+        Ok(())
+    }
+
+    fn load_all(&self) -> io::Result<Vec<StoredItem<T>>> {
+        Ok(self.items.lock().iter().cloned().collect())
+    }
+
+    fn next_due(&self) -> Option<SystemTime> {
+        self.items.lock().iter().map(|stored| stored.deadline).min()
+    }
+}
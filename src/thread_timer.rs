@@ -0,0 +1,170 @@
+use crate::clock::{Clock, Timer};
+use parking_lot::{Condvar, Mutex};
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, OnceLock},
+    task::{Context, Poll, Waker},
+    thread,
+    time::Instant,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Ожидающие футуры сна, упорядоченные по времени срабатывания
+#[derive(Default)]
+struct Sleepers {
+    /// Время срабатывания и идентификатор футуры
+    wakers: BTreeMap<(Instant, u64), Waker>,
+
+    /// Счетчик идентификаторов футур сна
+    next_id: u64,
+}
+
+/// Общее состояние фонового потока таймера
+#[derive(Default)]
+struct Shared {
+    sleepers: Mutex<Sleepers>,
+    condvar: Condvar,
+}
+
+/// Общее на весь процесс состояние, поток запускается при первом обращении
+fn shared() -> &'static Arc<Shared> {
+    static SHARED: OnceLock<Arc<Shared>> = OnceLock::new();
+
+    SHARED.get_or_init(|| {
+        let shared = Arc::new(Shared::default());
+
+        thread::Builder::new()
+            .name("delayed-queue-timer".to_owned())
+            .spawn({
+                let shared = shared.clone();
+                move || run(&shared)
+            })
+            .expect("Timer thread spawn failed");
+
+        shared
+    })
+}
+
+/// Цикл фонового потока: будим сработавшие футуры и спим до ближайшей
+fn run(shared: &Shared) {
+    let mut sleepers = shared.sleepers.lock();
+
+    loop {
+        let now = Instant::now();
+
+        // Забираем все сработавшие футуры
+        let mut wakers = Vec::new();
+        while let Some(entry) = sleepers.wakers.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+            wakers.push(entry.remove());
+        }
+
+        // Будим уже без блокировки
+        if !wakers.is_empty() {
+            drop(sleepers);
+            wakers.into_iter().for_each(Waker::wake);
+            sleepers = shared.sleepers.lock();
+            continue;
+        }
+
+        match sleepers.wakers.keys().next().map(|(deadline, _)| *deadline) {
+            Some(deadline) => {
+                shared.condvar.wait_until(&mut sleepers, deadline);
+            }
+            None => {
+                shared.condvar.wait(&mut sleepers);
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Clock with a built-in timer running on a background thread,
+/// works with any executor. The thread is spawned on first use and shared by all queues.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadClock;
+
+impl Timer for ThreadClock {
+    type Sleep = ThreadSleep;
+
+    fn sleep_until(&self, deadline: Instant) -> Self::Sleep {
+        let id = {
+            let mut sleepers = shared().sleepers.lock();
+            sleepers.next_id += 1;
+            sleepers.next_id
+        };
+
+        ThreadSleep {
+            deadline,
+            id,
+            registered: false,
+        }
+    }
+}
+
+impl Clock for ThreadClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Sleep future of [`ThreadClock`].
+#[derive(Debug)]
+pub struct ThreadSleep {
+    /// До какого момента спим
+    deadline: Instant,
+
+    /// Идентификатор в списке ожидающих
+    id: u64,
+
+    /// Зарегистрирована ли футура в потоке таймера
+    registered: bool,
+}
+
+impl Future for ThreadSleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let shared = shared();
+        let mut sleepers = shared.sleepers.lock();
+
+        if Instant::now() >= self.deadline {
+            sleepers.wakers.remove(&(self.deadline, self.id));
+            self.registered = false;
+            return Poll::Ready(());
+        }
+
+        // Регистрируем пробуждение, заменяя прошлое
+        let prev = sleepers
+            .wakers
+            .insert((self.deadline, self.id), cx.waker().clone());
+        self.registered = true;
+
+        // Новая футура может оказаться ближайшей - пусть поток пересчитает время сна
+        if prev.is_none() {
+            shared.condvar.notify_one();
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for ThreadSleep {
+    fn drop(&mut self) {
+        if self.registered {
+            shared()
+                .sleepers
+                .lock()
+                .wakers
+                .remove(&(self.deadline, self.id));
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use parking_lot::Mutex;
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll, Waker},
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Ожидающая футура
+struct Waiter {
+    /// Идентификатор футуры
+    future_id: u64,
+
+    /// Чем будить футуру
+    waker: Waker,
+
+    /// Было ли уже уведомление, которое футура еще не забрала
+    notified: bool,
+}
+
+/// Список футур, ждущих изменения очереди: свободного итема или свободного места.
+/// В отличие от condvar не требует аллокации футуры ожидания на каждое засыпание:
+/// записи живут в одном векторе, емкость которого переиспользуется.
+#[derive(Default)]
+pub(super) struct WaitList {
+    waiters: Mutex<Vec<Waiter>>,
+
+    /// Сколько футур еще ждет уведомления, чтобы не брать блокировку зря
+    pending: AtomicUsize,
+}
+
+impl WaitList {
+    /// Регистрируем ожидание, вызывается под блокировкой очереди,
+    /// поэтому уведомление после изменения очереди не потеряется
+    pub(super) fn register(&self, future_id: u64, cx: &Context<'_>) {
+        let mut waiters = self.waiters.lock();
+
+        match waiters
+            .iter_mut()
+            .find(|waiter| waiter.future_id == future_id)
+        {
+            Some(waiter) => update_waker(&mut waiter.waker, cx),
+            None => {
+                waiters.push(Waiter {
+                    future_id,
+                    waker: cx.waker().clone(),
+                    notified: false,
+                });
+                self.pending.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Проверяем, пришло ли уведомление, забирая его
+    pub(super) fn poll_notified(&self, future_id: u64, cx: &Context<'_>) -> Poll<()> {
+        let mut waiters = self.waiters.lock();
+
+        let Some(index) = waiters
+            .iter()
+            .position(|waiter| waiter.future_id == future_id)
+        else {
+            return Poll::Ready(());
+        };
+
+        if waiters[index].notified {
+            waiters.remove(index);
+            Poll::Ready(())
+        } else {
+            update_waker(&mut waiters[index].waker, cx);
+            Poll::Pending
+        }
+    }
+
+    /// Будим самую давнюю из еще не уведомленных футур, возвращая, нашлась ли такая
+    pub(super) fn notify_one(&self) -> bool {
+        // Регистрация идет под блокировкой очереди, а уведомление - после ее изменения,
+        // поэтому ждущую футуру мы здесь точно увидим
+        if self.pending.load(Ordering::SeqCst) == 0 {
+            return false;
+        }
+
+        let mut waiters = self.waiters.lock();
+
+        let Some(waiter) = waiters.iter_mut().find(|waiter| !waiter.notified) else {
+            return false;
+        };
+        waiter.notified = true;
+        self.pending.fetch_sub(1, Ordering::SeqCst);
+        let waker = waiter.waker.clone();
+
+        // Будим уже без блокировки
+        drop(waiters);
+
+        waker.wake();
+
+        true
+    }
+
+    /// Будим все еще не уведомленные футуры
+    pub(super) fn notify_all(&self) {
+        if self.pending.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+
+        let mut waiters = self.waiters.lock();
+
+        let wakers = waiters
+            .iter_mut()
+            .filter(|waiter| !waiter.notified)
+            .map(|waiter| {
+                waiter.notified = true;
+                waiter.waker.clone()
+            })
+            .collect::<Vec<_>>();
+        self.pending.fetch_sub(wakers.len(), Ordering::SeqCst);
+
+        // Будим уже без блокировки
+        drop(waiters);
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Отменяем ожидание, непрочитанное уведомление передаем следующей футуре
+    pub(super) fn cancel(&self, future_id: u64) {
+        let mut waiters = self.waiters.lock();
+
+        let Some(index) = waiters
+            .iter()
+            .position(|waiter| waiter.future_id == future_id)
+        else {
+            return;
+        };
+
+        let notified = waiters.remove(index).notified;
+        if !notified {
+            self.pending.fetch_sub(1, Ordering::SeqCst);
+        }
+
+        drop(waiters);
+
+        if notified {
+            self.notify_one();
+        }
+    }
+}
+
+/// Обновляем waker, только если футуру перекинули на другую задачу
+fn update_waker(waker: &mut Waker, cx: &Context<'_>) {
+    if !waker.will_wake(cx.waker()) {
+        *waker = cx.waker().clone();
+    }
+}
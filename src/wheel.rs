@@ -0,0 +1,250 @@
+use crate::item::DelayItem;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Сколько бит тика приходится на один уровень колеса
+const SLOT_BITS: u32 = 6;
+
+/// Количество слотов на уровне
+const SLOTS: usize = 1 << SLOT_BITS;
+
+/// Количество уровней колеса
+const LEVELS: usize = 6;
+
+/// Сколько тиков покрывает колесо целиком, дальние итемы лежат в переполнении
+const MAX_TICKS: u64 = 1 << (SLOT_BITS as usize * LEVELS);
+
+/// Индекс корзины переполнения
+const OVERFLOW: usize = LEVELS * SLOTS;
+
+/// Индекс корзины наступивших итемов
+const READY: usize = OVERFLOW + 1;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Иерархическое колесо таймеров.
+///
+/// Слот уровня `k` покрывает `64^k` тиков. Итем кладется на уровень по старшему биту,
+/// которым его тик отличается от текущего, поэтому корзины, просмотренные
+/// по уровням и слотам начиная с текущего, идут строго по возрастанию времени.
+/// При продвижении времени прошедшие корзины раскладываются заново на нижние уровни,
+/// добавление и извлечение итема стоят O(1) независимо от размера очереди.
+/// Наступившие итемы копятся в отдельной корзине, которую продвижение не трогает,
+/// иначе отстающие получатели перекладывали бы весь накопившийся хвост на каждом тике.
+pub(super) struct TimingWheel<T> {
+    /// Длительность тика в наносекундах
+    resolution: u128,
+
+    /// Начало отсчета тиков
+    start: Instant,
+
+    /// Текущий тик колеса
+    elapsed: u64,
+
+    /// Корзины итемов: уровни по порядку, затем переполнение и наступившие
+    buckets: Vec<VecDeque<DelayItem<T>>>,
+
+    /// Маски непустых слотов по уровням
+    occupied: [u64; LEVELS],
+
+    /// Общее количество итемов
+    len: usize,
+}
+
+impl<T> TimingWheel<T> {
+    pub(super) fn new(resolution: Duration, start: Instant) -> TimingWheel<T> {
+        assert!(!resolution.is_zero(), "Resolution should be positive");
+
+        TimingWheel {
+            resolution: resolution.as_nanos(),
+            start,
+            elapsed: 0,
+            buckets: (0..=READY).map(|_| VecDeque::new()).collect(),
+            occupied: [0; LEVELS],
+            len: 0,
+        }
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Тик, на который приходится момент времени
+    fn tick(&self, time: Instant) -> u64 {
+        let ticks = time.saturating_duration_since(self.start).as_nanos() / self.resolution;
+        u64::try_from(ticks).unwrap_or(u64::MAX)
+    }
+
+    /// Кладем итем в корзину по его времени, итемы текущего тика и просроченные
+    /// идут в корзину наступивших
+    pub(super) fn push(&mut self, queue_item: DelayItem<T>) {
+        let when = self.tick(queue_item.pop_time);
+
+        let bucket = match level_for(self.elapsed, when) {
+            _ if when <= self.elapsed => READY,
+            Some(level) => {
+                let slot = slot_for(when, level);
+                self.occupied[level] |= 1 << slot;
+                level * SLOTS + slot
+            }
+            None => OVERFLOW,
+        };
+
+        self.buckets[bucket].push_back(queue_item);
+        self.len += 1;
+    }
+
+    /// Продвигаем колесо к текущему моменту, раскладывая наступившие корзины заново
+    pub(super) fn advance(&mut self, now: Instant) {
+        let old = self.elapsed;
+        let new = self.tick(now);
+
+        if new <= old {
+            return;
+        }
+
+        self.elapsed = new;
+
+        // Собираем наступившие корзины по возрастанию времени, чтобы после раскладки
+        // итемы одного тика остались в порядке добавления.
+        // Корзины нулевого уровня уже упорядочены по тикам, а в корзинах старших уровней
+        // итемы лежат вперемешку, их сортируем: просроченные попадут в одну корзину.
+        let mut due = Vec::new();
+        let mut cascaded = Vec::new();
+
+        for level in 0..LEVELS {
+            let old_slot = slot_for(old, level);
+            let shift = SLOT_BITS as usize * (level + 1);
+
+            // Внутри того же окна старшего уровня наступили только слоты до нового текущего,
+            // иначе прошло все окно уровня целиком
+            let last_slot = if old >> shift == new >> shift {
+                slot_for(new, level)
+            } else {
+                SLOTS - 1
+            };
+
+            for slot in old_slot..=last_slot {
+                if self.occupied[level] & (1 << slot) != 0 {
+                    self.occupied[level] &= !(1 << slot);
+                    let items = std::mem::take(&mut self.buckets[level * SLOTS + slot]);
+                    if level == 0 {
+                        due.extend(items);
+                    } else {
+                        cascaded.extend(items);
+                    }
+                }
+            }
+        }
+
+        // Дальние итемы могли попасть в пределы колеса
+        if (old ^ new) >> (SLOT_BITS as usize * (LEVELS - 1)) != 0 {
+            cascaded.extend(std::mem::take(&mut self.buckets[OVERFLOW]));
+        }
+
+        cascaded.sort_by_key(|queue_item| queue_item.pop_time);
+
+        for queue_item in due.into_iter().chain(cascaded) {
+            self.len -= 1;
+            self.push(queue_item);
+        }
+    }
+
+    /// Непустые корзины в порядке возрастания времени
+    fn buckets_in_order(&self) -> impl Iterator<Item = usize> + '_ {
+        std::iter::once(READY).chain(
+            (0..LEVELS)
+                .flat_map(move |level| {
+                    let mut mask =
+                        self.occupied[level] & (u64::MAX << slot_for(self.elapsed, level));
+                    std::iter::from_fn(move || {
+                        if mask == 0 {
+                            return None;
+                        }
+                        let slot = mask.trailing_zeros() as usize;
+                        mask &= mask - 1;
+                        Some(level * SLOTS + slot)
+                    })
+                })
+                .chain(std::iter::once(OVERFLOW)),
+        )
+    }
+
+    /// Ищем самый ранний подходящий итем.
+    /// Наступившие итемы и итемы одного тика на нулевом уровне отдаем в порядке добавления,
+    /// на старших уровнях корзина покрывает много тиков, поэтому ищем в ней минимум.
+    pub(super) fn position<P>(&self, mut predicate: P) -> Option<(usize, usize)>
+    where
+        P: FnMut(&DelayItem<T>) -> bool,
+    {
+        self.buckets_in_order().find_map(|bucket| {
+            let items = &self.buckets[bucket];
+
+            if bucket < SLOTS || bucket == READY {
+                items.iter().position(&mut predicate)
+            } else {
+                items
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, queue_item)| predicate(queue_item))
+                    .min_by_key(|(_, queue_item)| queue_item.pop_time)
+                    .map(|(index, _)| index)
+            }
+            .map(|index| (bucket, index))
+        })
+    }
+
+    pub(super) fn get(&self, (bucket, index): (usize, usize)) -> &DelayItem<T> {
+        &self.buckets[bucket][index]
+    }
+
+    pub(super) fn get_mut(&mut self, (bucket, index): (usize, usize)) -> &mut DelayItem<T> {
+        &mut self.buckets[bucket][index]
+    }
+
+    pub(super) fn remove(&mut self, (bucket, index): (usize, usize)) -> DelayItem<T> {
+        let queue_item = self.buckets[bucket]
+            .remove(index)
+            .expect("Item should exist");
+        self.len -= 1;
+
+        if bucket < OVERFLOW && self.buckets[bucket].is_empty() {
+            self.occupied[bucket / SLOTS] &= !(1 << (bucket % SLOTS));
+        }
+
+        queue_item
+    }
+
+    /// Все итемы с их позициями в порядке извлечения
+    pub(super) fn iter(&self) -> impl Iterator<Item = ((usize, usize), &DelayItem<T>)> {
+        self.buckets_in_order().flat_map(move |bucket| {
+            self.buckets[bucket]
+                .iter()
+                .enumerate()
+                .map(move |(index, queue_item)| ((bucket, index), queue_item))
+        })
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Уровень для тика `when` относительно текущего `elapsed`, `None` для переполнения
+fn level_for(elapsed: u64, when: u64) -> Option<usize> {
+    let masked = (elapsed ^ when) | (SLOTS as u64 - 1);
+
+    if masked >= MAX_TICKS {
+        return None;
+    }
+
+    let significant = 63 - masked.leading_zeros() as usize;
+    Some(significant / SLOT_BITS as usize)
+}
+
+/// Слот тика на уровне
+fn slot_for(tick: u64, level: usize) -> usize {
+    ((tick >> (SLOT_BITS as usize * level)) as usize) & (SLOTS - 1)
+}
@@ -1,11 +1,25 @@
-use std::time::Duration;
-use tokio_delayed_queue::DelayedQueue;
+use std::{
+    future::Future,
+    marker::PhantomPinned,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering as AtomicOrdering},
+        mpsc, Arc, Mutex, OnceLock,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant, SystemTime},
+};
+use tokio_delayed_queue::{
+    Backend, Backoff, Clock, DelayStore, DelayedQueue, Jitter, ManualClock, ManualSleep,
+    MemoryStore, MissedTickBehavior, OverflowPolicy, PushError, RandomSource, RetryQueue,
+    ShardedDelayedQueue, StoredItem, Timer,
+};
 
 #[tokio::test]
 async fn test_func() {
     let queue = DelayedQueue::new(16);
-    queue.push(1, Duration::from_secs(1)).await;
-    queue.push(1, Duration::from_secs(2)).await;
+    queue.push(1, Duration::from_secs(1)).await.unwrap();
+    queue.push(1, Duration::from_secs(2)).await.unwrap();
 
     let v = queue.pop().await;
     assert_eq!(v, 1);
@@ -46,10 +60,10 @@ async fn test_func() {
         }
     });
 
-    queue.push(1, Duration::from_secs(3)).await;
-    queue.push(1, Duration::from_secs(4)).await;
-    queue.push(1, Duration::from_secs(4)).await;
-    queue.push(1, Duration::from_secs(5)).await;
+    queue.push(1, Duration::from_secs(3)).await.unwrap();
+    queue.push(1, Duration::from_secs(4)).await.unwrap();
+    queue.push(1, Duration::from_secs(4)).await.unwrap();
+    queue.push(1, Duration::from_secs(5)).await.unwrap();
 
     j1.await.unwrap();
     j2.await.unwrap();
@@ -58,3 +72,1400 @@ async fn test_func() {
     let v = queue.pop().await;
     assert_eq!(v, 1);
 }
+
+#[tokio::test]
+async fn test_periodic() {
+    let queue = DelayedQueue::new(4);
+    let handle = queue
+        .push_periodic(
+            1,
+            Duration::from_millis(10),
+            Duration::from_millis(20),
+            MissedTickBehavior::Skip,
+        )
+        .await
+        .unwrap();
+
+    let start = Instant::now();
+    for _ in 0..3 {
+        let v = queue.pop().await;
+        assert_eq!(v, 1);
+    }
+    assert!(start.elapsed() >= Duration::from_millis(50));
+
+    handle.stop();
+    assert!(handle.is_stopped());
+
+    let res = tokio::time::timeout(Duration::from_millis(100), queue.pop()).await;
+    assert!(res.is_err());
+
+    // После долгого простоя пропущенных периодов больше u32::MAX,
+    // следующий тик все равно выравнивается в будущее
+    let clock = ManualClock::new();
+    let queue = DelayedQueue::builder(4).clock(clock.clone()).build();
+    let handle = queue
+        .push_periodic(
+            1,
+            Duration::ZERO,
+            Duration::from_nanos(1),
+            MissedTickBehavior::Skip,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(queue.pop().await, 1);
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(queue.pop().await, 1);
+
+    let res = tokio::time::timeout(Duration::from_millis(20), queue.pop()).await;
+    assert!(res.is_err());
+    handle.stop();
+}
+
+#[cfg(feature = "cron")]
+#[tokio::test]
+async fn test_cron() {
+    use std::str::FromStr;
+    use tokio_delayed_queue::Schedule;
+
+    let queue = DelayedQueue::new(4);
+    let handle = queue
+        .push_cron(1, Schedule::from_str("* * * * * *").unwrap())
+        .await
+        .unwrap();
+
+    let v = queue.pop().await;
+    assert_eq!(v, 1);
+    let v = queue.pop().await;
+    assert_eq!(v, 1);
+
+    handle.stop();
+
+    let past = Schedule::from_str("0 0 0 1 1 * 2000").unwrap();
+    assert_eq!(
+        queue.push_cron(2, past).await.unwrap_err().into_inner(),
+        Some(2)
+    );
+}
+
+#[tokio::test]
+async fn test_retry() {
+    let dead = Arc::new(Mutex::new(Vec::new()));
+    let backoff = Backoff::Linear {
+        initial: Duration::from_millis(10),
+        step: Duration::from_millis(10),
+        max: Duration::from_millis(15),
+    };
+    let queue = RetryQueue::new(4, backoff, 3).with_dead_letter({
+        let dead = dead.clone();
+        move |item, attempts| dead.lock().unwrap().push((item, attempts))
+    });
+
+    queue.push(7, Duration::ZERO).await;
+
+    let start = Instant::now();
+    for attempt in 1..=3 {
+        let job = queue.pop().await;
+        assert_eq!(job.attempt(), attempt);
+        assert_eq!(*job.item(), 7);
+        assert_eq!(queue.retry(job).await, attempt < 3);
+    }
+    assert!(start.elapsed() >= Duration::from_millis(25));
+    assert_eq!(*dead.lock().unwrap(), vec![(7, 3)]);
+
+    let jitter = RetryQueue::new(
+        4,
+        Backoff::DecorrelatedJitter {
+            base: Duration::from_millis(1),
+            max: Duration::from_millis(20),
+        },
+        2,
+    );
+    jitter.push(1, Duration::ZERO).await;
+    let job = jitter.pop().await;
+    assert!(jitter.retry(job).await);
+    assert_eq!(jitter.pop().await.into_inner(), 1);
+
+    // Задержка первой попытки не считается предыдущей задержкой отсрочки
+    struct Max;
+    impl RandomSource for Max {
+        fn next_u64(&mut self) -> u64 {
+            u64::MAX
+        }
+    }
+
+    let jitter = RetryQueue::new(
+        4,
+        Backoff::DecorrelatedJitter {
+            base: Duration::from_millis(1),
+            max: Duration::from_secs(10),
+        },
+        2,
+    )
+    .with_random_source(Max);
+    jitter.push(1, Duration::from_millis(100)).await;
+    let job = jitter.pop().await;
+
+    let start = Instant::now();
+    assert!(jitter.retry(job).await);
+    assert_eq!(jitter.pop().await.into_inner(), 1);
+    assert!(start.elapsed() < Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn test_jitter() {
+    struct Zero;
+    impl RandomSource for Zero {
+        fn next_u64(&mut self) -> u64 {
+            0
+        }
+    }
+
+    let queue = DelayedQueue::builder(4)
+        .jitter(Jitter::Full)
+        .random_source(Zero)
+        .build();
+
+    let start = Instant::now();
+    queue.push(1, Duration::from_secs(10)).await.unwrap();
+    assert_eq!(queue.pop().await, 1);
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    queue
+        .push_with_jitter(2, Duration::from_millis(100), Jitter::Equal)
+        .await
+        .unwrap();
+    assert_eq!(queue.pop().await, 2);
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(50) && elapsed < Duration::from_millis(100));
+
+    queue
+        .push_with_jitter(
+            3,
+            Duration::from_millis(100),
+            Jitter::Bounded { percent: 20 },
+        )
+        .await
+        .unwrap();
+    assert_eq!(queue.pop().await, 3);
+    assert!(start.elapsed() >= elapsed + Duration::from_millis(80));
+}
+
+#[tokio::test]
+async fn test_pop_ack() {
+    let queue = DelayedQueue::builder(4)
+        .visibility_timeout(Duration::from_millis(50))
+        .build();
+    queue.push(1, Duration::ZERO).await.unwrap();
+    queue.push(2, Duration::ZERO).await.unwrap();
+
+    // Dropped without ack, returns immediately to the tail
+    let lease = queue.pop_ack().await;
+    assert_eq!(*lease, 1);
+    drop(lease);
+
+    let lease = queue.pop_ack().await;
+    assert_eq!(*lease, 2);
+    assert!(lease.nack(Duration::from_millis(10)));
+
+    let lease = queue.pop_ack().await;
+    assert_eq!(*lease, 1);
+    assert!(lease.ack());
+
+    // Visibility timeout expires, item is popped again
+    let start = Instant::now();
+    let stale = queue.pop_ack().await;
+    assert_eq!(*stale, 2);
+    let lease = queue.pop_ack().await;
+    assert_eq!(*lease, 2);
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert!(!stale.ack());
+    assert!(lease.ack());
+
+    let res = tokio::time::timeout(Duration::from_millis(100), queue.pop()).await;
+    assert!(res.is_err());
+}
+
+#[tokio::test]
+async fn test_max_lateness() {
+    let dead = Arc::new(Mutex::new(Vec::new()));
+    let queue = DelayedQueue::builder(4)
+        .max_lateness(Duration::from_millis(50), {
+            let dead = dead.clone();
+            move |item| dead.lock().unwrap().push(item)
+        })
+        .build();
+
+    queue.push(1, Duration::ZERO).await.unwrap();
+    queue.push(2, Duration::from_millis(200)).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Опоздание проверяется только при извлечении
+    assert_eq!(queue.len(), 2);
+    assert_eq!(queue.diverted_count(), 0);
+
+    assert_eq!(queue.pop().await, 2);
+    assert_eq!(*dead.lock().unwrap(), vec![1]);
+    assert_eq!(queue.diverted_count(), 1);
+}
+
+#[tokio::test]
+async fn test_ttl() {
+    let expired = Arc::new(Mutex::new(Vec::new()));
+    let queue = DelayedQueue::builder(4)
+        .on_expired({
+            let expired = expired.clone();
+            move |item| expired.lock().unwrap().push(item)
+        })
+        .build();
+
+    // Expires before it is due
+    queue
+        .push_with_ttl(1, Duration::from_millis(100), Duration::from_millis(50))
+        .await
+        .unwrap();
+    queue
+        .push_with_ttl(2, Duration::from_millis(10), Duration::from_millis(50))
+        .await
+        .unwrap();
+    queue
+        .push_with_ttl(3, Duration::from_millis(10), Duration::from_millis(500))
+        .await
+        .unwrap();
+
+    // Expires while waiting in the queue
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert_eq!(queue.pop().await, 3);
+    assert_eq!(*expired.lock().unwrap(), vec![1, 2]);
+    assert_eq!(queue.expired_count(), 2);
+}
+
+#[tokio::test]
+async fn test_memory_store() {
+    let store = Arc::new(MemoryStore::new());
+
+    let queue = DelayedQueue::builder(4)
+        .build_with_store(store.clone())
+        .unwrap();
+    queue.push(1, Duration::ZERO).await.unwrap();
+    queue.push(2, Duration::from_millis(50)).await.unwrap();
+    queue.push(3, Duration::from_secs(60)).await.unwrap();
+    assert_eq!(queue.pop().await, 1);
+    assert_eq!(store.load_all().unwrap().len(), 2);
+    assert!(store.next_due().is_some());
+    drop(queue);
+
+    // Restart
+    let queue = DelayedQueue::builder(4)
+        .build_with_store(store.clone())
+        .unwrap();
+    assert_eq!(queue.pop().await, 2);
+    assert_eq!(
+        store
+            .load_all()
+            .unwrap()
+            .into_iter()
+            .map(|stored| stored.item)
+            .collect::<Vec<_>>(),
+        vec![3]
+    );
+}
+
+/// Хранилище, проверяющее, что его зовут без блокировки очереди
+struct ProbeStore {
+    store: MemoryStore<u32>,
+    queue: Arc<OnceLock<DelayedQueue<u32>>>,
+    locked: Arc<AtomicBool>,
+    fail_insert: bool,
+}
+
+impl ProbeStore {
+    // Очередь под блокировкой не ответит на запрос из другого потока
+    fn probe(&self) {
+        let Some(queue) = self.queue.get().cloned() else {
+            return;
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || sender.send(queue.len()));
+
+        if receiver.recv_timeout(Duration::from_millis(100)).is_err() {
+            self.locked.store(true, AtomicOrdering::Relaxed);
+        }
+    }
+}
+
+impl DelayStore<u32> for ProbeStore {
+    fn insert(&self, key: u64, item: &u32, deadline: SystemTime) -> std::io::Result<()> {
+        self.probe();
+
+        if self.fail_insert {
+            return Err(std::io::Error::other("disk is full"));
+        }
+
+        self.store.insert(key, item, deadline)
+    }
+
+    fn remove(&self, key: u64) -> std::io::Result<()> {
+        self.probe();
+        self.store.remove(key)
+    }
+
+    fn load_all(&self) -> std::io::Result<Vec<StoredItem<u32>>> {
+        self.store.load_all()
+    }
+
+    fn next_due(&self) -> Option<SystemTime> {
+        self.store.next_due()
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_store_outside_lock() {
+    let build = |fail_insert| {
+        let queue = Arc::new(OnceLock::new());
+        let locked = Arc::new(AtomicBool::new(false));
+        let store = ProbeStore {
+            store: MemoryStore::new(),
+            queue: queue.clone(),
+            locked: locked.clone(),
+            fail_insert,
+        };
+
+        let built = DelayedQueue::builder(4)
+            .visibility_timeout(Duration::from_secs(60))
+            .build_with_store(store)
+            .unwrap();
+        queue.set(built.clone()).ok().unwrap();
+
+        (built, locked)
+    };
+
+    // Добавление, извлечение, повторение и аренда пишут в хранилище без блокировки очереди
+    let (queue, locked) = build(false);
+    queue.push(1, Duration::ZERO).await.unwrap();
+    let handle = queue
+        .push_periodic(
+            2,
+            Duration::ZERO,
+            Duration::from_secs(60),
+            MissedTickBehavior::Burst,
+        )
+        .await
+        .unwrap();
+
+    let lease = queue.pop_ack().await;
+    assert_eq!(*lease, 1);
+    lease.nack(Duration::ZERO);
+
+    let lease = queue.pop_ack().await;
+    assert_eq!(*lease, 2);
+    assert!(lease.ack());
+
+    assert_eq!(queue.pop().await, 1);
+    handle.stop();
+    assert!(!locked.load(AtomicOrdering::Relaxed));
+
+    // Ошибка записи возвращается отправителю вместе с итемом
+    let (queue, _) = build(true);
+    match queue.push(3, Duration::ZERO).await {
+        Err(PushError::Store(item, err)) => {
+            assert_eq!(item, 3);
+            assert_eq!(err.to_string(), "disk is full");
+        }
+        other => panic!("Unexpected push result {other:?}"),
+    }
+    assert!(queue.is_empty());
+}
+
+#[cfg(feature = "persistence")]
+#[tokio::test]
+async fn test_file_store() {
+    use tokio_delayed_queue::FileStore;
+
+    let path = std::env::temp_dir().join(format!("delayed_queue_{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let queue = DelayedQueue::builder(4)
+        .build_with_store(FileStore::open(&path).unwrap())
+        .unwrap();
+    queue.push("a".to_string(), Duration::ZERO).await.unwrap();
+    queue
+        .push("b".to_string(), Duration::from_millis(50))
+        .await
+        .unwrap();
+    assert_eq!(queue.pop().await, "a");
+    drop(queue);
+
+    // Restart
+    let store = FileStore::<String>::open(&path).unwrap();
+    assert!(store.next_due().is_some());
+    store.compact().unwrap();
+    let queue = DelayedQueue::builder(4).build_with_store(store).unwrap();
+    assert_eq!(queue.pop().await, "b");
+
+    let store = FileStore::<String>::open(&path).unwrap();
+    assert!(store.load_all().unwrap().is_empty());
+    assert!(store.next_due().is_none());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "persistence")]
+#[tokio::test]
+async fn test_file_store_torn_record() {
+    use std::io::Write;
+    use tokio_delayed_queue::FileStore;
+
+    let path = std::env::temp_dir().join(format!("delayed_queue_torn_{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let queue = DelayedQueue::builder(4)
+        .build_with_store(FileStore::open(&path).unwrap())
+        .unwrap();
+    queue
+        .push("a".to_string(), Duration::from_secs(3600))
+        .await
+        .unwrap();
+    drop(queue);
+
+    // Падение посреди записи
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap();
+    file.write_all(br#"{"Insert":{"key":2,"dead"#).unwrap();
+    drop(file);
+
+    // После перезапуска новые записи не склеиваются с оборванной
+    let queue = DelayedQueue::builder(4)
+        .build_with_store(FileStore::open(&path).unwrap())
+        .unwrap();
+    queue
+        .push("b".to_string(), Duration::from_secs(3600))
+        .await
+        .unwrap();
+    queue
+        .push("c".to_string(), Duration::from_secs(3600))
+        .await
+        .unwrap();
+    drop(queue);
+
+    let store = FileStore::<String>::open(&path).unwrap();
+    let items = store
+        .load_all()
+        .unwrap()
+        .into_iter()
+        .map(|stored| stored.item)
+        .collect::<Vec<_>>();
+    assert_eq!(items, ["a", "b", "c"]);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "serde")]
+#[tokio::test]
+async fn test_snapshot() {
+    use tokio_delayed_queue::QueueSnapshot;
+
+    let queue = DelayedQueue::new(4);
+    queue.push(1, Duration::from_millis(50)).await.unwrap();
+    queue
+        .push_with_ttl(2, Duration::from_millis(60), Duration::from_secs(10))
+        .await
+        .unwrap();
+
+    // Reserved by in-flight pop future
+    let pending = tokio::time::timeout(Duration::from_millis(10), queue.pop()).await;
+    assert!(pending.is_err());
+
+    let snapshot = queue.snapshot();
+    assert_eq!(snapshot.items.len(), 2);
+    assert!(snapshot.items[0].remaining <= Duration::from_millis(50));
+    assert!(snapshot.items[1].expires_at.is_some());
+
+    let json = serde_json::to_string(&snapshot).unwrap();
+    let snapshot: QueueSnapshot<i32> = serde_json::from_str(&json).unwrap();
+
+    let restored = DelayedQueue::restore(snapshot, 4);
+    assert_eq!(restored.pop().await, 1);
+    assert_eq!(restored.pop().await, 2);
+}
+
+#[tokio::test]
+async fn test_system_time() {
+    let queue = DelayedQueue::builder(4).recheck_wall_clock(true).build();
+    assert!(queue.is_empty());
+
+    let start = Instant::now();
+    let time = SystemTime::now() + Duration::from_millis(50);
+    queue.push_at_system_time(1, time).await.unwrap();
+    assert_eq!(queue.len(), 1);
+    assert_eq!(queue.next_deadline(), Some(time));
+
+    assert_eq!(queue.pop().await, 1);
+    assert!(start.elapsed() >= Duration::from_millis(45));
+    assert_eq!(queue.next_deadline(), None);
+}
+
+#[tokio::test]
+async fn test_wall_clock_jump() {
+    let clock = ManualClock::new();
+    let queue = DelayedQueue::builder(4)
+        .clock(clock.clone())
+        .recheck_wall_clock(true)
+        .build();
+
+    let hour = Duration::from_secs(3600);
+    let start = clock.system_now();
+    queue.push_at_system_time(1, start + hour).await.unwrap();
+
+    let ready = tokio::spawn({
+        let queue = queue.clone();
+        async move { queue.ready().await }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    // Часы прыгнули назад - срок по часам еще не настал
+    clock.set_system_time(start - hour);
+    clock.advance(hour);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!ready.is_finished());
+
+    // Часы прыгнули вперед - футура замечает это при периодической перепроверке
+    clock.set_system_time(start + hour * 2);
+    clock.advance(Duration::from_secs(1));
+    ready.await.unwrap();
+    assert_eq!(queue.pop().await, 1);
+
+    // Футура извлечения тоже не проспит скачок вперед
+    queue
+        .push_at_system_time(2, clock.system_now() + hour)
+        .await
+        .unwrap();
+
+    let popper = tokio::spawn({
+        let queue = queue.clone();
+        async move { queue.pop().await }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!popper.is_finished());
+
+    clock.set_system_time(clock.system_now() + hour);
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(popper.await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn test_manual_clock() {
+    let clock = ManualClock::new();
+    let queue = DelayedQueue::builder(4).clock(clock.clone()).build();
+
+    queue.push(1, Duration::from_secs(3600)).await.unwrap();
+    queue.push(2, Duration::from_secs(7200)).await.unwrap();
+
+    // Без продвижения часов ничего не достается
+    let pending = tokio::time::timeout(Duration::from_millis(20), queue.pop()).await;
+    assert!(pending.is_err());
+
+    let popper = tokio::spawn({
+        let queue = queue.clone();
+        async move { queue.pop().await }
+    });
+
+    tokio::task::yield_now().await;
+    clock.advance(Duration::from_secs(3600));
+    assert_eq!(popper.await.unwrap(), 1);
+
+    let pending = tokio::time::timeout(Duration::from_millis(20), queue.pop()).await;
+    assert!(pending.is_err());
+
+    clock.advance(Duration::from_secs(3600));
+    assert_eq!(queue.pop().await, 2);
+}
+
+/// Часы с футурой сна, которую нельзя перемещать после пиннинга
+#[derive(Clone)]
+struct PinnedClock(ManualClock);
+
+/// Футура сна, проверяющая, что ее не переместили между опросами
+struct PinnedSleep {
+    sleep: Pin<Box<ManualSleep>>,
+    address: Option<usize>,
+    _pinned: PhantomPinned,
+}
+
+impl Future for PinnedSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let address = &*self as *const PinnedSleep as usize;
+
+        // Safety: футуру не перемещаем, берем только ее поля
+        let this = unsafe { self.get_unchecked_mut() };
+        assert_eq!(*this.address.get_or_insert(address), address, "Sleep moved");
+
+        this.sleep.as_mut().poll(cx)
+    }
+}
+
+impl Timer for PinnedClock {
+    type Sleep = PinnedSleep;
+
+    fn sleep_until(&self, deadline: Instant) -> PinnedSleep {
+        PinnedSleep {
+            sleep: Box::pin(self.0.sleep_until(deadline)),
+            address: None,
+            _pinned: PhantomPinned,
+        }
+    }
+}
+
+impl Clock for PinnedClock {
+    fn now(&self) -> Instant {
+        self.0.now()
+    }
+}
+
+#[tokio::test]
+async fn test_pinned_sleep() {
+    let clock = ManualClock::new();
+    let queue = DelayedQueue::builder(4)
+        .clock(PinnedClock(clock.clone()))
+        .build();
+
+    queue.push(1, Duration::from_secs(10)).await.unwrap();
+    queue.push(2, Duration::from_secs(20)).await.unwrap();
+
+    // Футура сна живет внутри запиненной футуры извлечения и опрашивается много раз
+    let mut pop = std::pin::pin!(queue.pop());
+    for _ in 0..3 {
+        tokio::select! {
+            _ = &mut pop => panic!("Item should not be due yet"),
+            _ = tokio::task::yield_now() => {}
+        }
+    }
+
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(pop.await, 1);
+
+    let mut ready = std::pin::pin!(queue.ready());
+    tokio::select! {
+        _ = &mut ready => panic!("Item should not be due yet"),
+        _ = tokio::task::yield_now() => {}
+    }
+
+    clock.advance(Duration::from_secs(10));
+    ready.await;
+    assert_eq!(queue.pop_with_meta().await.item, 2);
+}
+
+#[cfg(feature = "thread-timer")]
+#[tokio::test]
+async fn test_thread_clock() {
+    let queue = DelayedQueue::builder(4)
+        .clock(tokio_delayed_queue::ThreadClock)
+        .build();
+
+    let start = Instant::now();
+    queue.push(2, Duration::from_millis(60)).await.unwrap();
+    queue.push(1, Duration::from_millis(30)).await.unwrap();
+
+    assert_eq!(queue.pop().await, 2);
+    assert_eq!(queue.pop().await, 1);
+    assert!(start.elapsed() >= Duration::from_millis(55));
+}
+
+#[cfg(feature = "async-io")]
+#[test]
+fn test_async_io_clock() {
+    let queue = DelayedQueue::builder(4)
+        .clock(tokio_delayed_queue::AsyncIoClock)
+        .build();
+
+    let start = Instant::now();
+    async_io::block_on(async {
+        queue.push(1, Duration::from_millis(30)).await.unwrap();
+        assert_eq!(queue.pop().await, 1);
+    });
+    assert!(start.elapsed() >= Duration::from_millis(25));
+}
+
+#[tokio::test]
+async fn test_fair() {
+    const CONSUMERS: usize = 4;
+    const ITEMS: usize = 400;
+
+    let queue = DelayedQueue::builder(8).fair(true).build();
+    let counts = Arc::new(Mutex::new([0usize; CONSUMERS]));
+
+    let consumers = (0..CONSUMERS)
+        .map(|index| {
+            let queue = queue.clone();
+            let counts = counts.clone();
+            tokio::spawn(async move {
+                loop {
+                    queue.pop().await;
+                    counts.lock().unwrap()[index] += 1;
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for item in 0..ITEMS {
+        queue.push(item, Duration::ZERO).await.unwrap();
+    }
+
+    while counts.lock().unwrap().iter().sum::<usize>() < ITEMS {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    consumers.iter().for_each(|consumer| consumer.abort());
+
+    // Каждый получатель обслуживается по очереди, никто не голодает
+    let counts = *counts.lock().unwrap();
+    for count in counts {
+        assert!(count >= ITEMS / CONSUMERS / 2, "{counts:?}");
+    }
+}
+
+#[tokio::test]
+async fn test_parallel_reserve() {
+    let queue = DelayedQueue::new(4);
+
+    let start = Instant::now();
+    queue.push(1, Duration::from_millis(150)).await.unwrap();
+    queue.push(2, Duration::from_millis(30)).await.unwrap();
+
+    let poppers = (0..2)
+        .map(|_| {
+            let queue = queue.clone();
+            tokio::spawn(async move { (queue.pop().await, start.elapsed()) })
+        })
+        .collect::<Vec<_>>();
+
+    let mut popped = Vec::new();
+    for popper in poppers {
+        popped.push(popper.await.unwrap());
+    }
+    popped.sort();
+
+    // Второй итем не ждет первого, каждый получатель ждет свой итем
+    assert_eq!(popped[0].0, 1);
+    assert!(popped[0].1 >= Duration::from_millis(145));
+    assert_eq!(popped[1].0, 2);
+    assert!(popped[1].1 >= Duration::from_millis(25));
+    assert!(popped[1].1 < Duration::from_millis(120));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_contended() {
+    const CONSUMERS: usize = 4;
+    const ITEMS: usize = 4000;
+
+    let queue = DelayedQueue::new(8);
+
+    let consumers = (0..CONSUMERS)
+        .map(|_| {
+            let queue = queue.clone();
+            tokio::spawn(async move {
+                for _ in 0..ITEMS / CONSUMERS {
+                    // Отменяем часть ожиданий, уведомление не должно потеряться
+                    let cancelled = tokio::time::timeout(Duration::ZERO, queue.pop()).await;
+                    if cancelled.is_err() {
+                        queue.pop().await;
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // Получатели и отправитель ждут друг друга на полной и пустой очереди
+    let done = tokio::time::timeout(Duration::from_secs(10), async {
+        for item in 0..ITEMS {
+            queue.push(item, Duration::ZERO).await.unwrap();
+        }
+        for consumer in consumers {
+            consumer.await.unwrap();
+        }
+    })
+    .await;
+    assert!(done.is_ok());
+    assert!(queue.is_empty());
+}
+
+#[tokio::test]
+async fn test_sharded() {
+    let queue = ShardedDelayedQueue::new(16, 3);
+    assert_eq!(queue.shards_count(), 3);
+    assert_eq!(queue.capacity(), 16);
+
+    // Итемы попадают в разные шарды, но отдаются по времени готовности
+    queue.push(3, Duration::from_millis(60)).await.unwrap();
+    queue.push(1, Duration::from_millis(20)).await.unwrap();
+    queue.push(2, Duration::from_millis(40)).await.unwrap();
+    queue
+        .push_hashed("key", 4, Duration::from_millis(80))
+        .await
+        .unwrap();
+    assert_eq!(queue.len(), 4);
+
+    for expected in 1..=4 {
+        assert_eq!(queue.pop().await, expected);
+    }
+    assert!(queue.is_empty());
+}
+
+#[tokio::test]
+async fn test_timing_wheel() {
+    let clock = ManualClock::new();
+    let queue = DelayedQueue::builder(16)
+        .clock(clock.clone())
+        .backend(Backend::TimingWheel {
+            resolution: Duration::from_millis(1),
+        })
+        .build();
+
+    // Итемы на разных уровнях колеса и за его пределами, добавляем в обратном порядке
+    let delays = [
+        Duration::from_secs(1000 * 24 * 3600),
+        Duration::from_secs(2 * 3600),
+        Duration::from_secs(5),
+        Duration::from_millis(70),
+        Duration::from_millis(5),
+        Duration::ZERO,
+    ];
+    for (item, delay) in delays.into_iter().enumerate() {
+        queue.push(delays.len() - item, delay).await.unwrap();
+    }
+    assert_eq!(queue.len(), delays.len());
+
+    assert_eq!(queue.pop().await, 1);
+
+    let pending = tokio::time::timeout(Duration::from_millis(20), queue.pop()).await;
+    assert!(pending.is_err());
+
+    clock.advance(Duration::from_millis(70));
+    assert_eq!(queue.pop().await, 2);
+    assert_eq!(queue.pop().await, 3);
+
+    // Наступившие корзины раскладываются заново, итемы по-прежнему идут по времени
+    clock.advance(Duration::from_secs(1001 * 24 * 3600));
+    for expected in 4..=6 {
+        assert_eq!(queue.pop().await, expected);
+    }
+    assert!(queue.is_empty());
+}
+
+#[tokio::test]
+async fn test_resolution() {
+    let clock = ManualClock::new();
+    let queue = DelayedQueue::builder(8)
+        .clock(clock.clone())
+        .resolution(Duration::from_millis(10))
+        .build();
+
+    for (item, delay) in [(1, 1), (2, 3), (3, 7)] {
+        queue
+            .push(item, Duration::from_millis(delay))
+            .await
+            .unwrap();
+    }
+
+    let poppers = (0..3)
+        .map(|_| {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.pop().await })
+        })
+        .collect::<Vec<_>>();
+
+    // Сроки округлены вверх до слота, раньше ничего не отдается
+    tokio::task::yield_now().await;
+    clock.advance(Duration::from_millis(7));
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(poppers.iter().all(|popper| !popper.is_finished()));
+
+    // Одно пробуждение отдает все итемы слота
+    clock.advance(Duration::from_millis(3));
+
+    let mut popped = Vec::new();
+    for popper in poppers {
+        popped.push(popper.await.unwrap());
+    }
+    popped.sort();
+    assert_eq!(popped, [1, 2, 3]);
+}
+
+#[tokio::test]
+async fn test_max_weight() {
+    let queue = DelayedQueue::builder(16)
+        .max_weight(10, |item: &String| item.len())
+        .build();
+
+    queue
+        .push("aaaa".to_string(), Duration::ZERO)
+        .await
+        .unwrap();
+    queue
+        .push("bbbbbb".to_string(), Duration::ZERO)
+        .await
+        .unwrap();
+
+    // Итем тяжелее всего бюджета возвращается сразу же
+    match queue.push_cancellable("c".repeat(11), Duration::ZERO).await {
+        Err(PushError::TooHeavy(item)) => assert_eq!(item.len(), 11),
+        other => panic!("Unexpected push result {other:?}"),
+    }
+
+    // Места по весу нет, хотя по количеству его хватает
+    let pusher = tokio::spawn({
+        let queue = queue.clone();
+        async move { queue.push("dd".to_string(), Duration::ZERO).await }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!pusher.is_finished());
+    assert_eq!(queue.len(), 2);
+
+    assert_eq!(queue.pop().await, "aaaa");
+    pusher.await.unwrap().unwrap();
+
+    assert_eq!(queue.pop().await, "bbbbbb");
+    assert_eq!(queue.pop().await, "dd");
+
+    // Обычное добавление тоже возвращает слишком тяжелый итем, а не паникует
+    match queue.push("e".repeat(11), Duration::ZERO).await {
+        Err(PushError::TooHeavy(item)) => assert_eq!(item.len(), 11),
+        other => panic!("Unexpected push result {other:?}"),
+    }
+    assert!(queue.is_empty());
+    assert_eq!(queue.dropped_count(), 0);
+}
+
+#[tokio::test]
+async fn test_overflow_policy() {
+    async fn fill(policy: OverflowPolicy) -> (DelayedQueue<u32>, Arc<Mutex<Vec<u32>>>) {
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let queue = DelayedQueue::builder(2)
+            .overflow_policy(policy)
+            .on_evicted({
+                let evicted = evicted.clone();
+                move |item| evicted.lock().unwrap().push(item)
+            })
+            .build();
+
+        queue.push(1, Duration::from_millis(30)).await.unwrap();
+        queue.push(2, Duration::from_millis(10)).await.unwrap();
+
+        (queue, evicted)
+    }
+
+    // Новый итем возвращается обратно
+    let (queue, _) = fill(OverflowPolicy::Reject).await;
+    match queue.push_cancellable(3, Duration::ZERO).await {
+        Err(PushError::Full(item)) => assert_eq!(item, 3),
+        other => panic!("Unexpected push result {other:?}"),
+    }
+
+    // Периодический итем тоже возвращается, а не отдает живой хендл
+    let pushed = queue
+        .push_periodic(
+            3,
+            Duration::ZERO,
+            Duration::from_secs(1),
+            MissedTickBehavior::Burst,
+        )
+        .await;
+    match pushed {
+        Err(PushError::Full(item)) => assert_eq!(item, 3),
+        Err(err) => panic!("Unexpected push error {err:?}"),
+        Ok(_) => panic!("Periodic push should fail"),
+    }
+    assert_eq!(queue.len(), 2);
+    assert_eq!(queue.dropped_count(), 0);
+
+    // Выкидываем новый итем, добавление сообщает об этом
+    let (queue, evicted) = fill(OverflowPolicy::DropNewest).await;
+    let pushed = queue.push_cancellable(3, Duration::ZERO).await;
+    assert!(matches!(pushed, Err(PushError::Dropped)));
+    assert_eq!(*evicted.lock().unwrap(), [3]);
+    assert_eq!(queue.dropped_count(), 1);
+    assert_eq!(queue.len(), 2);
+
+    // Вытесняем самый давний итем
+    let (queue, evicted) = fill(OverflowPolicy::DropOldest).await;
+    queue.push(3, Duration::ZERO).await.unwrap();
+    assert_eq!(*evicted.lock().unwrap(), [1]);
+    assert_eq!(queue.pop().await, 2);
+    assert_eq!(queue.pop().await, 3);
+
+    // Вытесняем итем с самым поздним сроком, новый итем с еще более поздним выкидываем
+    let (queue, evicted) = fill(OverflowPolicy::EvictLatestDeadline).await;
+    queue.push(3, Duration::ZERO).await.unwrap();
+    let pushed = queue.push(4, Duration::from_secs(60)).await;
+    assert!(matches!(pushed, Err(PushError::Dropped)));
+    assert_eq!(*evicted.lock().unwrap(), [1, 4]);
+    assert_eq!(queue.dropped_count(), 2);
+    assert_eq!(queue.pop().await, 2);
+    assert_eq!(queue.pop().await, 3);
+}
+
+#[tokio::test]
+async fn test_evict_by_weight() {
+    let evicted = Arc::new(Mutex::new(Vec::new()));
+    let queue = DelayedQueue::builder(16)
+        .max_weight(10, |item: &usize| *item)
+        .overflow_policy(OverflowPolicy::EvictLatestDeadline)
+        .on_evicted({
+            let evicted = evicted.clone();
+            move |item| evicted.lock().unwrap().push(item)
+        })
+        .build();
+
+    queue.push(5, Duration::from_secs(10)).await.unwrap();
+    queue.push(4, Duration::from_secs(30)).await.unwrap();
+
+    // Вытеснение одного позднего итема не освобождает веса, поэтому выкидываем только новый
+    let pushed = queue.push_cancellable(6, Duration::from_secs(20)).await;
+    assert!(matches!(pushed, Err(PushError::Dropped)));
+    assert_eq!(*evicted.lock().unwrap(), [6]);
+    assert_eq!(queue.len(), 2);
+
+    // Поздних итемов хватает - вытесняем их, начиная с самого позднего
+    queue.push(1, Duration::ZERO).await.unwrap();
+    queue.push(9, Duration::from_millis(20)).await.unwrap();
+    assert_eq!(*evicted.lock().unwrap(), [6, 4, 5]);
+    assert_eq!(queue.pop().await, 1);
+    assert_eq!(queue.pop().await, 9);
+}
+
+#[tokio::test]
+async fn test_set_capacity() {
+    let queue = DelayedQueue::new(1);
+    queue.push(1, Duration::ZERO).await.unwrap();
+
+    let spawn_push = |item| {
+        let queue = queue.clone();
+        tokio::spawn(async move { queue.push(item, Duration::ZERO).await })
+    };
+
+    // Увеличение емкости будит всех ждущих отправителей
+    let pushers = [spawn_push(2), spawn_push(3)];
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(queue.len(), 1);
+
+    queue.set_capacity(3);
+    for pusher in pushers {
+        pusher.await.unwrap().unwrap();
+    }
+    assert_eq!(queue.len(), 3);
+
+    // Уменьшение ничего не выкидывает, добавление ждет, пока очередь не станет меньше
+    queue.set_capacity(1);
+    assert_eq!(queue.capacity(), 1);
+    assert_eq!(queue.len(), 3);
+
+    let pusher = spawn_push(4);
+    assert_eq!(queue.pop().await, 1);
+    assert_eq!(queue.pop().await, 2);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!pusher.is_finished());
+
+    assert_eq!(queue.pop().await, 3);
+    pusher.await.unwrap().unwrap();
+    assert_eq!(queue.pop().await, 4);
+}
+
+#[tokio::test]
+async fn test_shrink_capacity_with_eviction() {
+    let evicted = Arc::new(Mutex::new(Vec::new()));
+    let queue = DelayedQueue::builder(8)
+        .overflow_policy(OverflowPolicy::DropOldest)
+        .on_evicted({
+            let evicted = evicted.clone();
+            move |item| evicted.lock().unwrap().push(item)
+        })
+        .build();
+
+    for item in 0..8 {
+        queue.push(item, Duration::ZERO).await.unwrap();
+    }
+
+    // Сверх уменьшенной емкости добавление ждет, а не вытесняет лишние итемы
+    queue.set_capacity(2);
+    let pusher = tokio::spawn({
+        let queue = queue.clone();
+        async move { queue.push(8, Duration::ZERO).await }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!pusher.is_finished());
+    assert_eq!(queue.len(), 8);
+
+    for item in 0..6 {
+        assert_eq!(queue.pop().await, item);
+    }
+
+    // Дальше обычная политика: вытесняется один самый давний итем
+    pusher.await.unwrap().unwrap();
+    assert_eq!(*evicted.lock().unwrap(), [6]);
+    assert_eq!(queue.pop().await, 7);
+    assert_eq!(queue.pop().await, 8);
+}
+
+#[tokio::test]
+async fn test_push_future() {
+    let queue = DelayedQueue::new(1);
+    queue.push(1, Duration::ZERO).await.unwrap();
+
+    // Отмена ждущего добавления возвращает итем и не трогает очередь
+    let mut push = queue.push_cancellable(2, Duration::ZERO);
+    tokio::select! {
+        _ = &mut push => panic!("Push should wait for space"),
+        _ = tokio::time::sleep(Duration::from_millis(20)) => {}
+    }
+    assert_eq!(push.into_inner(), Some(2));
+    assert_eq!(queue.len(), 1);
+
+    // Брошенная футура отдает уведомление следующей
+    let dropped = tokio::time::timeout(
+        Duration::from_millis(20),
+        queue.push_cancellable(3, Duration::ZERO),
+    );
+    assert!(dropped.await.is_err());
+
+    let pusher = {
+        let queue = queue.clone();
+        tokio::spawn(async move { queue.push_cancellable(4, Duration::ZERO).await })
+    };
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!pusher.is_finished());
+
+    assert_eq!(queue.pop().await, 1);
+    let key = pusher.await.unwrap().unwrap();
+
+    // Футура отдает ключ добавленного итема
+    let popped = queue.pop_with_meta().await;
+    assert_eq!((popped.item, popped.key), (4, key));
+    assert!(queue.is_empty());
+}
+
+#[tokio::test]
+async fn test_ready() {
+    let queue = DelayedQueue::builder(4)
+        .backend(Backend::TimingWheel {
+            resolution: Duration::from_millis(1),
+        })
+        .build();
+
+    // Ждет срока итема, но не забирает его
+    let start = Instant::now();
+    queue.push(1, Duration::from_millis(50)).await.unwrap();
+    queue.ready().await;
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert_eq!(queue.len(), 1);
+    assert_eq!(queue.pop().await, 1);
+
+    // Итем, добавленный во время ожидания, учитывается
+    let ready = {
+        let queue = queue.clone();
+        tokio::spawn(async move { queue.ready().await })
+    };
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!ready.is_finished());
+
+    let start = Instant::now();
+    queue.push(2, Duration::from_secs(10)).await.unwrap();
+    queue.push(3, Duration::from_millis(10)).await.unwrap();
+    ready.await.unwrap();
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(queue.pop().await, 3);
+
+    // Срок оставшегося итема еще не настал
+    let res = tokio::time::timeout(Duration::from_millis(50), queue.ready()).await;
+    assert!(res.is_err());
+    assert_eq!(queue.len(), 1);
+}
+
+#[tokio::test]
+async fn test_pop_with_meta() {
+    let clock = ManualClock::new();
+    let queue = DelayedQueue::builder(4).clock(clock.clone()).build();
+
+    queue.push(1, Duration::from_secs(10)).await.unwrap();
+
+    // Футуры резервируют итем и отменяются, не дождавшись его
+    for _ in 0..2 {
+        let pending = tokio::time::timeout(Duration::from_millis(20), queue.pop()).await;
+        assert!(pending.is_err());
+    }
+
+    clock.advance(Duration::from_secs(15));
+
+    let popped = queue.pop_with_meta().await;
+    assert_eq!(popped.item, 1);
+    assert_eq!(popped.key, 1);
+    assert_eq!(
+        popped.pop_time - popped.enqueued_at,
+        Duration::from_secs(10)
+    );
+    assert_eq!(popped.queued_for(), Duration::from_secs(15));
+    assert_eq!(popped.lateness(), Duration::from_secs(5));
+    assert_eq!(popped.cancellations, 2);
+
+    // Новый итем получает свой ключ, отмен у него не было
+    queue.push(2, Duration::ZERO).await.unwrap();
+    let popped = queue.pop_with_meta().await;
+    assert_eq!(popped.key, 2);
+    assert_eq!(popped.cancellations, 0);
+    assert_eq!(popped.into_inner(), 2);
+}
+
+#[tokio::test]
+async fn test_pop_moves_reservation() {
+    let queue = DelayedQueue::builder(4)
+        .backend(Backend::TimingWheel {
+            resolution: Duration::from_millis(1),
+        })
+        .build();
+    queue.push(1, Duration::from_millis(100)).await.unwrap();
+
+    // Футура резервирует поздний итем, а потом забирает более ранний
+    let pop = {
+        let queue = queue.clone();
+        tokio::spawn(async move { queue.pop_with_meta().await })
+    };
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    queue.push(2, Duration::ZERO).await.unwrap();
+    let popped = pop.await.unwrap();
+    assert_eq!(popped.item, 2);
+
+    // Никто не отменялся, резервирование позднего итема снято без отмены
+    let popped = queue.pop_with_meta().await;
+    assert_eq!(popped.item, 1);
+    assert_eq!(popped.cancellations, 0);
+}
+
+#[tokio::test]
+async fn test_lease_wakes_sleeping_pop() {
+    let queue = DelayedQueue::builder(4)
+        .visibility_timeout(Duration::from_secs(5))
+        .build();
+    queue.push(1, Duration::ZERO).await.unwrap();
+
+    let spawn_pop = || {
+        let queue = queue.clone();
+        tokio::spawn(async move { queue.pop().await })
+    };
+
+    // Ждущая футура спит на копии итема до конца таймаута видимости,
+    // брошенная аренда возвращает итем сразу же
+    let lease = queue.pop_ack().await;
+    let popper = spawn_pop();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    drop(lease);
+    let popped = tokio::time::timeout(Duration::from_secs(1), popper).await;
+    assert_eq!(popped.unwrap().unwrap(), 1);
+
+    // Подтверждение убирает копию, и футура дожидается следующего итема
+    queue.push(2, Duration::ZERO).await.unwrap();
+    let lease = queue.pop_ack().await;
+    let popper = spawn_pop();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(lease.ack());
+    queue.push(3, Duration::ZERO).await.unwrap();
+    let popped = tokio::time::timeout(Duration::from_secs(1), popper).await;
+    assert_eq!(popped.unwrap().unwrap(), 3);
+}
+
+#[tokio::test]
+async fn test_evict_reserved() {
+    let queue = DelayedQueue::builder(1)
+        .overflow_policy(OverflowPolicy::DropOldest)
+        .build();
+    queue.push(1, Duration::from_secs(5)).await.unwrap();
+
+    // Футура зарезервировала итем и спит до его срока
+    let popper = {
+        let queue = queue.clone();
+        tokio::spawn(async move { queue.pop().await })
+    };
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    // Вытеснение будит ее, и она забирает новый итем
+    queue.push(2, Duration::ZERO).await.unwrap();
+    let popped = tokio::time::timeout(Duration::from_secs(1), popper).await;
+    assert_eq!(popped.unwrap().unwrap(), 2);
+    assert_eq!(queue.dropped_count(), 1);
+}
+
+#[tokio::test]
+async fn test_timing_wheel_earlier_push() {
+    let queue = DelayedQueue::builder(4)
+        .backend(Backend::TimingWheel {
+            resolution: Duration::from_millis(1),
+        })
+        .build();
+    queue.push(1, Duration::from_secs(5)).await.unwrap();
+
+    // Футура зарезервировала итем и спит до его срока
+    let popper = {
+        let queue = queue.clone();
+        tokio::spawn(async move { queue.pop().await })
+    };
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    // Итем раньше будит ее, порядок сроков сохраняется
+    queue.push(2, Duration::from_millis(10)).await.unwrap();
+    let popped = tokio::time::timeout(Duration::from_secs(1), popper).await;
+    assert_eq!(popped.unwrap().unwrap(), 2);
+    assert_eq!(queue.len(), 1);
+}
+
+#[tokio::test]
+async fn test_fair_sleeping_head() {
+    let queue = DelayedQueue::builder(4).fair(true).build();
+    queue.push(1, Duration::from_secs(5)).await.unwrap();
+    queue.push(2, Duration::ZERO).await.unwrap();
+
+    // Первая футура спит на своем итеме и не задерживает следующую
+    let head = {
+        let queue = queue.clone();
+        tokio::spawn(async move { queue.pop().await })
+    };
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    let popped = tokio::time::timeout(Duration::from_secs(1), queue.pop()).await;
+    assert_eq!(popped.unwrap(), 2);
+    assert!(!head.is_finished());
+    head.abort();
+}
+
+#[tokio::test]
+async fn test_resolution_idle_owner() {
+    let clock = ManualClock::new();
+    let queue = DelayedQueue::builder(4)
+        .clock(clock.clone())
+        .resolution(Duration::from_millis(100))
+        .build();
+    queue.push(1, Duration::from_millis(30)).await.unwrap();
+    queue.push(2, Duration::from_millis(60)).await.unwrap();
+
+    // Владелец заводит таймер слота, а его задачу больше никто не полит
+    let mut owner = std::pin::pin!(queue.pop());
+    let pending = tokio::time::timeout(Duration::from_millis(10), &mut owner).await;
+    assert!(pending.is_err());
+
+    // Вторая футура ждет тот же слот вместе с ним
+    let follower = {
+        let queue = queue.clone();
+        tokio::spawn(async move { queue.pop().await })
+    };
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!follower.is_finished());
+
+    // Таймер слота будит ее сам
+    clock.advance(Duration::from_millis(100));
+    let popped = tokio::time::timeout(Duration::from_secs(1), follower).await;
+    assert_eq!(popped.unwrap().unwrap(), 2);
+    assert_eq!(owner.await, 1);
+}